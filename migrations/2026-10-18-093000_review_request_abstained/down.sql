@@ -0,0 +1 @@
+ALTER TABLE fcp_review_request DROP COLUMN abstained;
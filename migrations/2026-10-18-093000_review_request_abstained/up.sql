@@ -0,0 +1 @@
+ALTER TABLE fcp_review_request ADD COLUMN abstained BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,8 @@
+DELETE FROM fcp_proposal WHERE cancelled_at IS NOT NULL;
+
+DROP INDEX fcp_proposal_live_fk_issue_key;
+ALTER TABLE fcp_proposal ADD CONSTRAINT fcp_proposal_fk_issue_key UNIQUE (fk_issue);
+
+ALTER TABLE fcp_proposal DROP COLUMN fk_cancelling_comment;
+ALTER TABLE fcp_proposal DROP COLUMN fk_cancelled_by;
+ALTER TABLE fcp_proposal DROP COLUMN cancelled_at;
@@ -0,0 +1,8 @@
+ALTER TABLE fcp_proposal ADD COLUMN cancelled_at TIMESTAMP WITHOUT TIME ZONE;
+ALTER TABLE fcp_proposal ADD COLUMN fk_cancelled_by INTEGER REFERENCES githubuser (id);
+ALTER TABLE fcp_proposal ADD COLUMN fk_cancelling_comment INTEGER REFERENCES issuecomment (id);
+
+-- an issue can have any number of cancelled proposals, but only one live one
+ALTER TABLE fcp_proposal DROP CONSTRAINT fcp_proposal_fk_issue_key;
+CREATE UNIQUE INDEX fcp_proposal_live_fk_issue_key ON fcp_proposal (fk_issue)
+    WHERE cancelled_at IS NULL;
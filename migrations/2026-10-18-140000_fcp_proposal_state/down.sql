@@ -0,0 +1,6 @@
+DROP TABLE fcp_state_transition;
+
+ALTER TABLE fcp_proposal ADD COLUMN fcp_closed BOOLEAN NOT NULL DEFAULT false;
+UPDATE fcp_proposal SET fcp_closed = state IN ('finished', 'resolved');
+ALTER TABLE fcp_proposal DROP CONSTRAINT fcp_proposal_state_check;
+ALTER TABLE fcp_proposal DROP COLUMN state;
@@ -0,0 +1,33 @@
+ALTER TABLE fcp_proposal ADD COLUMN state VARCHAR(16) NOT NULL DEFAULT 'proposed';
+UPDATE fcp_proposal SET state = CASE
+    WHEN cancelled_at IS NOT NULL THEN 'cancelled'
+    WHEN fcp_closed THEN 'finished'
+    WHEN fcp_start IS NOT NULL THEN 'in-fcp'
+    ELSE 'proposed'
+END;
+ALTER TABLE fcp_proposal ALTER COLUMN state DROP DEFAULT;
+ALTER TABLE fcp_proposal ADD CONSTRAINT fcp_proposal_state_check
+    CHECK (state IN ('proposed', 'in-fcp', 'finished', 'resolved', 'cancelled'));
+ALTER TABLE fcp_proposal DROP COLUMN fcp_closed;
+
+CREATE TABLE fcp_state_transition (
+    id SERIAL PRIMARY KEY,
+    fk_proposal INTEGER NOT NULL REFERENCES fcp_proposal (id),
+    -- NULL when the proposal was created
+    from_state VARCHAR(16),
+    to_state VARCHAR(16) NOT NULL,
+    transitioned_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+);
+
+-- keep what we know about past transitions, starting with each proposal's creation by its
+-- initiating comment
+INSERT INTO fcp_state_transition (fk_proposal, from_state, to_state, transitioned_at)
+    SELECT p.id, NULL, 'proposed', c.created_at
+    FROM fcp_proposal p JOIN issuecomment c ON c.id = p.fk_initiating_comment
+    ORDER BY p.id;
+INSERT INTO fcp_state_transition (fk_proposal, from_state, to_state, transitioned_at)
+    SELECT id, 'proposed', 'in-fcp', fcp_start FROM fcp_proposal WHERE fcp_start IS NOT NULL;
+INSERT INTO fcp_state_transition (fk_proposal, from_state, to_state, transitioned_at)
+    SELECT id, CASE WHEN fcp_start IS NULL THEN 'proposed' ELSE 'in-fcp' END, 'cancelled',
+           cancelled_at
+    FROM fcp_proposal WHERE cancelled_at IS NOT NULL;
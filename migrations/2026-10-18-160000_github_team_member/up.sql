@@ -0,0 +1,8 @@
+-- members of the GitHub teams which mscbot.toml teams take their members from
+CREATE TABLE github_team_member (
+    id SERIAL PRIMARY KEY,
+    -- org/team-slug
+    team VARCHAR NOT NULL,
+    fk_member INTEGER NOT NULL REFERENCES githubuser (id),
+    UNIQUE (team, fk_member)
+);
@@ -0,0 +1,2 @@
+DELETE FROM fcp_review_request WHERE NOT active;
+ALTER TABLE fcp_review_request DROP COLUMN active;
@@ -0,0 +1,2 @@
+-- reviewers who are no longer on a tagged team keep their review, in case they come back
+ALTER TABLE fcp_review_request ADD COLUMN active BOOLEAN NOT NULL DEFAULT TRUE;
@@ -0,0 +1,15 @@
+-- the old index allowed a single uncancelled proposal per issue, so this can't be rolled back
+-- once an issue has gone through more than one FCP
+DO $$
+BEGIN
+    IF EXISTS (SELECT fk_issue FROM fcp_proposal WHERE cancelled_at IS NULL
+               GROUP BY fk_issue HAVING COUNT(*) > 1) THEN
+        RAISE EXCEPTION 'some issues have more than one uncancelled FCP proposal, which the '
+                        'previous schema can''t hold';
+    END IF;
+END
+$$;
+
+DROP INDEX fcp_proposal_live_fk_issue_key;
+CREATE UNIQUE INDEX fcp_proposal_live_fk_issue_key ON fcp_proposal (fk_issue)
+    WHERE cancelled_at IS NULL;
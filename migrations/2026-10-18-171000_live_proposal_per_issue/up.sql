@@ -0,0 +1,4 @@
+-- finished and resolved proposals are history too, so a new proposal can follow them
+DROP INDEX fcp_proposal_live_fk_issue_key;
+CREATE UNIQUE INDEX fcp_proposal_live_fk_issue_key ON fcp_proposal (fk_issue)
+    WHERE state IN ('proposed', 'in-fcp');
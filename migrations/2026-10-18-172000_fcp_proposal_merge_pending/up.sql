@@ -0,0 +1,2 @@
+-- finished merge proposals whose pull request wasn't ready to be merged yet, to be retried
+ALTER TABLE fcp_proposal ADD COLUMN merge_pending BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,3 @@
+-- when the final comment period ends, fixed when it starts. Running FCPs which started before
+-- this was recorded still end after the duration their labels currently ask for
+ALTER TABLE fcp_proposal ADD COLUMN fcp_end TIMESTAMP WITHOUT TIME ZONE;
@@ -0,0 +1,276 @@
+use std::fs::File;
+use std::io::{self, Read, Write};
+
+use serde_json;
+
+use config::CONFIG;
+use domain::github::GitHubUser;
+use error::{DashError, DashResult};
+use github;
+use nag;
+use scraper;
+use teams::{self, SETUP};
+
+pub const USAGE: &'static str = "\
+Usage: mscbot-rs [COMMAND]
+
+Commands:
+    serve                      run the scraper and the web server (the default)
+    scrape-once [--repo REPO]  scrape GitHub once, only REPO (org/repo) if given
+    evaluate-nags              start and finish the final comment periods which are due
+    check-config [PATH]        validate mscbot.toml, or the file at PATH
+    export-users [FILE]        write the known GitHub users to FILE (or stdout) as JSON
+    import-users FILE          add the GitHub users in FILE (or stdin, if FILE is -),
+                               as written by export-users
+    show-fcp REPO#NUMBER       show the proposals on an issue, e.g. org/repo#123
+    help                       show this message
+";
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum Command {
+    Serve,
+    ScrapeOnce { repo: Option<String> },
+    EvaluateNags,
+    CheckConfig { path: Option<String> },
+    ExportUsers { path: Option<String> },
+    ImportUsers { path: String },
+    ShowFcp { repo: String, number: i32 },
+    Help,
+}
+
+impl Command {
+    /// Parse the command line arguments, without the name of the binary.
+    pub fn from_args<I: IntoIterator<Item = String>>(args: I) -> Result<Command, String> {
+        let mut args = args.into_iter();
+
+        let command = match args.next() {
+            None => return Ok(Command::Serve),
+            Some(command) => command,
+        };
+
+        let command = match &*command {
+            "serve" => Command::Serve,
+            "scrape-once" => {
+                let repo = match args.next() {
+                    None => None,
+                    Some(ref flag) if flag == "--repo" => {
+                        Some(args.next().ok_or("`--repo` needs a repository")?)
+                    }
+                    Some(ref flag) if flag.starts_with("--repo=") => {
+                        Some(flag["--repo=".len()..].to_string())
+                    }
+                    Some(other) => return Err(format!("unexpected argument `{}`", other)),
+                };
+                if let Some(ref repo) = repo {
+                    if repo.split('/').count() != 2 || repo.split('/').any(str::is_empty) {
+                        return Err(format!("`{}` isn't a repository like org/repo", repo));
+                    }
+                }
+                Command::ScrapeOnce { repo: repo }
+            }
+            "evaluate-nags" => Command::EvaluateNags,
+            "check-config" => Command::CheckConfig { path: args.next() },
+            "export-users" => Command::ExportUsers { path: args.next() },
+            "import-users" => {
+                Command::ImportUsers { path: args.next().ok_or("`import-users` needs a file")? }
+            }
+            "show-fcp" => {
+                let issue = args.next().ok_or("`show-fcp` needs an issue like org/repo#123")?;
+                let (repo, number) = parse_issue(&issue)?;
+                Command::ShowFcp { repo: repo, number: number }
+            }
+            "help" | "--help" | "-h" => Command::Help,
+            other => return Err(format!("unknown command `{}`", other)),
+        };
+
+        match args.next() {
+            Some(extra) => Err(format!("unexpected argument `{}`", extra)),
+            None => Ok(command),
+        }
+    }
+
+    /// Run a one-off command. `serve` is run by `main` instead, which has to set up the
+    /// background threads for it, so it's an error here.
+    pub fn run(self) -> DashResult<()> {
+        match self {
+            Command::Serve => {
+                throw!(DashError::Misc(Some("`serve` isn't a one-off command".to_string())))
+            }
+            Command::ScrapeOnce { repo } => scrape_once(repo),
+            Command::EvaluateNags => github::evaluate_all_nags(),
+            Command::CheckConfig { path } => check_config(path),
+            Command::ExportUsers { path } => export_users(path),
+            Command::ImportUsers { path } => import_users(&path),
+            Command::ShowFcp { repo, number } => show_fcp(&repo, number),
+            Command::Help => {
+                print!("{}", USAGE);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Split e.g. `org/repo#123` into the repository and the issue number.
+fn parse_issue(issue: &str) -> Result<(String, i32), String> {
+    let malformed = || format!("`{}` isn't an issue like org/repo#123", issue);
+
+    let mut parts = issue.splitn(2, '#');
+    let repo = parts.next().unwrap_or("");
+    let number = parts.next().and_then(|number| number.parse().ok()).ok_or_else(&malformed)?;
+
+    if repo.split('/').count() != 2 || repo.split('/').any(str::is_empty) {
+        return Err(malformed());
+    }
+
+    Ok((repo.to_string(), number))
+}
+
+fn scrape_once(repo: Option<String>) -> DashResult<()> {
+    scraper::refresh_team_members();
+
+    let since = github::most_recent_update()?;
+    match repo {
+        Some(repo) => {
+            if !SETUP.get().tracks_repo(&repo) {
+                throw!(DashError::Misc(Some(format!("{} isn't tracked in mscbot.toml", repo))));
+            }
+            // the other repos weren't scraped, so this doesn't count as a successful update
+            github::ingest_since(&repo, since)
+        }
+        None => {
+            scraper::scrape_github(since);
+            Ok(())
+        }
+    }
+}
+
+fn check_config(path: Option<String>) -> DashResult<()> {
+    let path = path.unwrap_or_else(|| CONFIG.mscbot_config_path.clone());
+    teams::check_mscbot_cfg(&path)?;
+    println!("{} is valid", path);
+    Ok(())
+}
+
+fn export_users(path: Option<String>) -> DashResult<()> {
+    let users = github::all_users()?;
+    let json = serde_json::to_string_pretty(&users)?;
+
+    match path {
+        Some(path) => {
+            File::create(&path)?.write_all(json.as_bytes())?;
+            info!("Exported {} users to {}", users.len(), path);
+        }
+        None => println!("{}", json),
+    }
+
+    Ok(())
+}
+
+fn import_users(path: &str) -> DashResult<()> {
+    let mut json = String::new();
+    if path == "-" {
+        io::stdin().read_to_string(&mut json)?;
+    } else {
+        File::open(path)?.read_to_string(&mut json)?;
+    }
+
+    let users = serde_json::from_str::<Vec<GitHubUser>>(&json)?;
+    github::import_users(&users)?;
+    info!("Imported {} users from {}", users.len(), path);
+
+    Ok(())
+}
+
+fn show_fcp(repo: &str, number: i32) -> DashResult<()> {
+    let history = nag::proposal_history(repo, number)?;
+    let issue = &history.issue;
+
+    println!("{}#{}: {}", issue.repository, issue.number, issue.title);
+    println!("{}, labels: {}",
+             if issue.open { "open" } else { "closed" },
+             issue.labels.join(", "));
+
+    if history.proposals.is_empty() {
+        println!("\nNo FCP has been proposed.");
+    }
+
+    for proposal in &history.proposals {
+        let fcp = &proposal.fcp;
+        println!("\nProposal {} to {} by @{}: {}",
+                 fcp.id, fcp.disposition, proposal.initiator.login, fcp.state);
+
+        if let Some(start) = fcp.fcp_start {
+            println!("  FCP started at {}", start);
+        }
+        if let Some(end) = fcp.fcp_end {
+            println!("  FCP ends at {}", end);
+        }
+        if let Some(ref cancelled_by) = proposal.cancelled_by {
+            println!("  cancelled by @{}", cancelled_by.login);
+        }
+
+        for transition in &proposal.transitions {
+            println!("  {} {} -> {}",
+                     transition.transitioned_at,
+                     transition.from_state.as_ref().map(|s| &**s).unwrap_or("(none)"),
+                     transition.to_state);
+        }
+
+        for &(ref reviewer, ref review) in &proposal.reviews {
+            let status = if review.abstained {
+                "abstained"
+            } else if review.reviewed {
+                "reviewed"
+            } else {
+                "pending"
+            };
+            println!("  @{}: {}", reviewer.login, status);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Command, String> {
+        Command::from_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn commands() {
+        assert_eq!(parse(&[]), Ok(Command::Serve));
+        assert_eq!(parse(&["serve"]), Ok(Command::Serve));
+        assert_eq!(parse(&["scrape-once"]), Ok(Command::ScrapeOnce { repo: None }));
+        assert_eq!(parse(&["scrape-once", "--repo", "org/repo"]),
+                   Ok(Command::ScrapeOnce { repo: Some("org/repo".to_string()) }));
+        assert_eq!(parse(&["scrape-once", "--repo=org/repo"]),
+                   Ok(Command::ScrapeOnce { repo: Some("org/repo".to_string()) }));
+        assert_eq!(parse(&["evaluate-nags"]), Ok(Command::EvaluateNags));
+        assert_eq!(parse(&["check-config"]), Ok(Command::CheckConfig { path: None }));
+        assert_eq!(parse(&["check-config", "other.toml"]),
+                   Ok(Command::CheckConfig { path: Some("other.toml".to_string()) }));
+        assert_eq!(parse(&["export-users"]), Ok(Command::ExportUsers { path: None }));
+        assert_eq!(parse(&["import-users", "users.json"]),
+                   Ok(Command::ImportUsers { path: "users.json".to_string() }));
+        assert_eq!(parse(&["show-fcp", "org/repo#123"]),
+                   Ok(Command::ShowFcp { repo: "org/repo".to_string(), number: 123 }));
+        assert_eq!(parse(&["--help"]), Ok(Command::Help));
+    }
+
+    #[test]
+    fn malformed_commands() {
+        assert_eq!(parse(&["scrap-once"]), Err("unknown command `scrap-once`".to_string()));
+        assert!(parse(&["scrape-once", "--repo"]).is_err());
+        assert!(parse(&["scrape-once", "--repo", "org"]).is_err());
+        assert!(parse(&["scrape-once", "org/repo"]).is_err());
+        assert!(parse(&["import-users"]).is_err());
+        assert!(parse(&["evaluate-nags", "now"]).is_err());
+        assert!(parse(&["show-fcp"]).is_err());
+        assert!(parse(&["show-fcp", "org/repo"]).is_err());
+        assert!(parse(&["show-fcp", "org/repo#abc"]).is_err());
+        assert!(parse(&["show-fcp", "repo#123"]).is_err());
+    }
+}
@@ -3,9 +3,6 @@
 use std::collections::BTreeMap;
 use std::env;
 
-pub const MSC_BOT_MENTION: &'static str = "@mscbot";
-pub const GH_ORGS: [&'static str; 1] = ["test-org-blabla"];
-
 lazy_static! {
     pub static ref CONFIG: Config = {
         match init() {
@@ -29,7 +26,10 @@ pub struct Config {
     pub github_user_agent: String,
     pub github_webhook_secrets: Vec<String>,
     pub github_interval_mins: u64,
+    pub nag_interval_mins: u64,
     pub post_comments: bool,
+    pub mscbot_config_path: String,
+    pub admin_token: Option<String>,
 }
 
 impl Config {
@@ -46,6 +46,15 @@ const GITHUB_WEBHOOK_SECRETS: &'static str = "GITHUB_WEBHOOK_SECRETS";
 const GITHUB_UA: &'static str = "GITHUB_USER_AGENT";
 const GITHUB_INTERVAL: &'static str = "GITHUB_SCRAPE_INTERVAL";
 const POST_COMMENTS: &'static str = "POST_COMMENTS";
+const NAG_INTERVAL: &'static str = "NAG_EVALUATION_INTERVAL";
+const MSCBOT_CONFIG: &'static str = "MSCBOT_CONFIG";
+const ADMIN_TOKEN: &'static str = "ADMIN_TOKEN";
+
+/// How often (in minutes) proposals are re-evaluated if `NAG_EVALUATION_INTERVAL` isn't set.
+const DEFAULT_NAG_INTERVAL_MINS: u64 = 10;
+
+/// Where `mscbot.toml` is read from if `MSCBOT_CONFIG` isn't set.
+const DEFAULT_MSCBOT_CONFIG_PATH: &'static str = "mscbot.toml";
 
 // this is complex, but we'll shortly need a lot more config items
 // so checking them automagically seems like a nice solution
@@ -83,6 +92,24 @@ pub fn init() -> Result<Config, Vec<&'static str>> {
         let post_comments = vars.remove(POST_COMMENTS).unwrap().parse::<bool>();
         let post_comments = ok_or!(post_comments, throw!(vec![POST_COMMENTS]));
 
+        // optional, unlike the others
+        let nag_interval = match env::var(NAG_INTERVAL) {
+            Ok(interval) => ok_or!(interval.parse::<u64>(), throw!(vec![NAG_INTERVAL])),
+            Err(_) => DEFAULT_NAG_INTERVAL_MINS,
+        };
+        if nag_interval == 0 {
+            throw!(vec![NAG_INTERVAL]);
+        }
+
+        let mscbot_config_path = env::var(MSCBOT_CONFIG)
+            .unwrap_or_else(|_| DEFAULT_MSCBOT_CONFIG_PATH.to_string());
+
+        // without a token, the admin endpoints are disabled
+        let admin_token = match env::var(ADMIN_TOKEN) {
+            Ok(token) => if token.is_empty() { None } else { Some(token) },
+            Err(_) => None,
+        };
+
         let webhook_secrets = vars.remove(GITHUB_WEBHOOK_SECRETS).unwrap();
         let webhook_secrets = webhook_secrets.split(',').map(String::from).collect();
 
@@ -93,7 +120,10 @@ pub fn init() -> Result<Config, Vec<&'static str>> {
                github_user_agent: gh_ua,
                github_webhook_secrets: webhook_secrets,
                github_interval_mins: gh_interval,
+               nag_interval_mins: nag_interval,
                post_comments: post_comments,
+               mscbot_config_path: mscbot_config_path,
+               admin_token: admin_token,
            })
 
     } else {
@@ -29,6 +29,14 @@ pub struct GitHubUser {
     pub login: String,
 }
 
+/// A member of a GitHub team, as last fetched by the scraper.
+#[derive(Clone, Debug, Insertable)]
+#[table_name="github_team_member"]
+pub struct NewGitHubTeamMember<'a> {
+    pub team: &'a str,
+    pub fk_member: i32,
+}
+
 #[derive(AsChangeset, Clone, Debug, Deserialize, Eq, Insertable,
          Ord, PartialEq, PartialOrd, Queryable)]
 #[table_name="milestone"]
@@ -1,7 +1,125 @@
 use chrono::NaiveDateTime;
 
+use error::{DashError, DashResult};
 use super::schema::*;
 
+/// Where a proposal is in its lifecycle.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FcpState {
+    /// Waiting for reviews or for concerns to be resolved.
+    Proposed,
+    /// In its final comment period.
+    InFcp,
+    /// The final comment period has elapsed.
+    Finished,
+    /// The disposition was carried out after the final comment period, e.g. the issue was
+    /// closed or postponed.
+    Resolved,
+    Cancelled,
+}
+
+const STATE_REPR_PROPOSED: &'static str = "proposed";
+const STATE_REPR_IN_FCP: &'static str = "in-fcp";
+const STATE_REPR_FINISHED: &'static str = "finished";
+const STATE_REPR_RESOLVED: &'static str = "resolved";
+const STATE_REPR_CANCELLED: &'static str = "cancelled";
+
+impl FcpState {
+    pub fn repr(self) -> &'static str {
+        match self {
+            FcpState::Proposed => STATE_REPR_PROPOSED,
+            FcpState::InFcp => STATE_REPR_IN_FCP,
+            FcpState::Finished => STATE_REPR_FINISHED,
+            FcpState::Resolved => STATE_REPR_RESOLVED,
+            FcpState::Cancelled => STATE_REPR_CANCELLED,
+        }
+    }
+
+    pub fn from_str(string: &str) -> DashResult<Self> {
+        Ok(match string {
+            STATE_REPR_PROPOSED => FcpState::Proposed,
+            STATE_REPR_IN_FCP => FcpState::InFcp,
+            STATE_REPR_FINISHED => FcpState::Finished,
+            STATE_REPR_RESOLVED => FcpState::Resolved,
+            STATE_REPR_CANCELLED => FcpState::Cancelled,
+            _ => throw!(DashError::Misc(None)),
+        })
+    }
+
+    /// The representations of the states in which a proposal can still change.
+    pub fn live_reprs() -> Vec<&'static str> {
+        vec![STATE_REPR_PROPOSED, STATE_REPR_IN_FCP]
+    }
+
+    /// Whether a proposal in this state can still change, i.e. its FCP isn't over yet.
+    pub fn is_live(self) -> bool {
+        match self {
+            FcpState::Proposed | FcpState::InFcp => true,
+            FcpState::Finished | FcpState::Resolved | FcpState::Cancelled => false,
+        }
+    }
+
+    /// Check that a proposal in this state can still be changed, with an explanation for the
+    /// people involved if it can't. `what` names the part of the proposal being changed.
+    pub fn ensure_live(self, what: &str) -> DashResult<()> {
+        if !self.is_live() {
+            throw!(DashError::Rejected(format!("the FCP is over (this proposal is {}), so {} \
+                                                can't be changed anymore",
+                                               self.description(),
+                                               what)));
+        }
+
+        Ok(())
+    }
+
+    /// Whether a proposal can move from this state to `next`. Staying in a live state is
+    /// allowed, e.g. when a concern is raised on a proposal which is still pending review.
+    pub fn can_transition_to(self, next: FcpState) -> bool {
+        use self::FcpState::*;
+
+        match (self, next) {
+            (Proposed, Proposed) | (Proposed, InFcp) | (Proposed, Cancelled) |
+            (InFcp, InFcp) | (InFcp, Proposed) | (InFcp, Finished) | (InFcp, Cancelled) |
+            (Finished, Resolved) => true,
+            _ => false,
+        }
+    }
+
+    /// Describes a proposal which is in this state.
+    pub fn description(self) -> &'static str {
+        match self {
+            FcpState::Proposed => "pending review",
+            FcpState::InFcp => "in its final comment period",
+            FcpState::Finished => "finished",
+            FcpState::Resolved => "resolved",
+            FcpState::Cancelled => "cancelled",
+        }
+    }
+
+    /// Describes moving a proposal into this state.
+    fn action(self) -> &'static str {
+        match self {
+            FcpState::Proposed => "sent back for review",
+            FcpState::InFcp => "put into its final comment period",
+            FcpState::Finished => "finished",
+            FcpState::Resolved => "resolved",
+            FcpState::Cancelled => "cancelled",
+        }
+    }
+
+    /// Check that a proposal can move from this state to `next`, with an explanation for the
+    /// people involved if it can't.
+    pub fn ensure_transition_to(self, next: FcpState) -> DashResult<()> {
+        if !self.can_transition_to(next) {
+            throw!(DashError::Rejected(format!("this proposal is {}, so it can't be {}",
+                                               self.description(),
+                                               next.action())));
+        }
+
+        Ok(())
+    }
+}
+
 #[derive(Clone, Debug, Eq, Ord, Insertable, PartialEq, PartialOrd)]
 #[table_name="fcp_proposal"]
 pub struct NewFcpProposal<'a> {
@@ -11,7 +129,7 @@ pub struct NewFcpProposal<'a> {
     pub disposition: &'a str,
     pub fk_bot_tracking_comment: i32,
     pub fcp_start: Option<NaiveDateTime>,
-    pub fcp_closed: bool,
+    pub state: &'a str,
 }
 
 #[derive(AsChangeset, Clone, Debug, Deserialize, Eq, Ord,
@@ -26,7 +144,40 @@ pub struct FcpProposal {
     pub disposition: String,
     pub fk_bot_tracking_comment: i32,
     pub fcp_start: Option<NaiveDateTime>,
-    pub fcp_closed: bool,
+    pub cancelled_at: Option<NaiveDateTime>,
+    pub fk_cancelled_by: Option<i32>,
+    pub fk_cancelling_comment: Option<i32>,
+    pub state: String,
+    /// Whether the proposal is finished, but its pull request wasn't ready to be merged yet, so
+    /// merging it is retried.
+    pub merge_pending: bool,
+    /// When the final comment period ends, fixed when it starts. `None` if it isn't running,
+    /// or if it started before we recorded this.
+    pub fcp_end: Option<NaiveDateTime>,
+}
+
+impl FcpProposal {
+    pub fn fcp_state(&self) -> DashResult<FcpState> {
+        FcpState::from_str(&self.state)
+    }
+}
+
+#[derive(Clone, Debug, Eq, Insertable, Ord, PartialEq, PartialOrd)]
+#[table_name="fcp_state_transition"]
+pub struct NewFcpStateTransition<'a> {
+    pub fk_proposal: i32,
+    pub from_state: Option<&'a str>,
+    pub to_state: &'a str,
+    pub transitioned_at: NaiveDateTime,
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd, Queryable, Serialize)]
+pub struct FcpStateTransition {
+    pub id: i32,
+    pub fk_proposal: i32,
+    pub from_state: Option<String>,
+    pub to_state: String,
+    pub transitioned_at: NaiveDateTime,
 }
 
 #[derive(Clone, Debug, Eq, Insertable, Ord, PartialEq, PartialOrd, Serialize)]
@@ -35,6 +186,10 @@ pub struct NewFcpReviewRequest {
     pub fk_proposal: i32,
     pub fk_reviewer: i32,
     pub reviewed: bool,
+    pub abstained: bool,
+    /// Whether the reviewer is still on a team tagged on the issue. Reviewers who aren't keep
+    /// their review, in case they're tagged again.
+    pub active: bool,
 }
 
 #[derive(AsChangeset, Clone, Debug, Deserialize, Eq, Ord,
@@ -45,6 +200,10 @@ pub struct FcpReviewRequest {
     pub fk_proposal: i32,
     pub fk_reviewer: i32,
     pub reviewed: bool,
+    pub abstained: bool,
+    /// Whether the reviewer is still on a team tagged on the issue. Reviewers who aren't keep
+    /// their review, in case they're tagged again.
+    pub active: bool,
 }
 
 #[derive(Clone, Debug, Eq, Insertable, Ord, PartialEq, PartialOrd)]
@@ -77,7 +236,8 @@ pub struct NewFeedbackRequest {
     pub fk_feedback_comment: Option<i32>,
 }
 
-#[derive(AsChangeset, Clone, Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd, Queryable)]
+#[derive(AsChangeset, Clone, Debug, Deserialize, Eq, Ord,
+         PartialEq, PartialOrd, Queryable, Serialize)]
 #[table_name="rfc_feedback_request"]
 pub struct FeedbackRequest {
     pub id: i32,
@@ -86,3 +246,70 @@ pub struct FeedbackRequest {
     pub fk_issue: i32,
     pub fk_feedback_comment: Option<i32>,
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const ALL_STATES: [FcpState; 5] = [FcpState::Proposed,
+                                       FcpState::InFcp,
+                                       FcpState::Finished,
+                                       FcpState::Resolved,
+                                       FcpState::Cancelled];
+
+    #[test]
+    fn state_repr_roundtrip() {
+        for &state in &ALL_STATES {
+            assert_eq!(FcpState::from_str(state.repr()).unwrap(), state);
+        }
+        assert!(FcpState::from_str("closed").is_err());
+    }
+
+    #[test]
+    fn live_states() {
+        for &state in &ALL_STATES {
+            assert_eq!(state.is_live(), FcpState::live_reprs().contains(&state.repr()));
+        }
+    }
+
+    #[test]
+    fn state_transitions() {
+        use self::FcpState::*;
+
+        assert!(Proposed.can_transition_to(InFcp));
+        assert!(InFcp.can_transition_to(Proposed));
+        assert!(InFcp.can_transition_to(Finished));
+        assert!(Finished.can_transition_to(Resolved));
+
+        // a finished FCP can't take new concerns or be cancelled
+        assert!(!Finished.can_transition_to(Proposed));
+        assert!(!Finished.can_transition_to(Cancelled));
+        // the final comment period can't be skipped
+        assert!(!Proposed.can_transition_to(Finished));
+
+        // nothing leaves the terminal states
+        for &next in &ALL_STATES {
+            assert!(!Resolved.can_transition_to(next));
+            assert!(!Cancelled.can_transition_to(next));
+        }
+    }
+
+    #[test]
+    fn illegal_transition_explained() {
+        match FcpState::Finished.ensure_transition_to(FcpState::Proposed) {
+            Err(DashError::Rejected(reason)) => {
+                assert_eq!(reason, "this proposal is finished, so it can't be sent back for review")
+            }
+            other => panic!("expected an explanation, got {:?}", other),
+        }
+
+        assert!(FcpState::InFcp.ensure_live("its reviews").is_ok());
+        match FcpState::Resolved.ensure_live("its reviews") {
+            Err(DashError::Rejected(reason)) => {
+                assert_eq!(reason, "the FCP is over (this proposal is resolved), so its reviews \
+                                    can't be changed anymore")
+            }
+            other => panic!("expected an explanation, got {:?}", other),
+        }
+    }
+}
@@ -18,7 +18,12 @@ table! {
         disposition -> Varchar,
         fk_bot_tracking_comment -> Int4,
         fcp_start -> Nullable<Timestamp>,
-        fcp_closed -> Bool,
+        cancelled_at -> Nullable<Timestamp>,
+        fk_cancelled_by -> Nullable<Int4>,
+        fk_cancelling_comment -> Nullable<Int4>,
+        state -> Varchar,
+        merge_pending -> Bool,
+        fcp_end -> Nullable<Timestamp>,
     }
 }
 
@@ -28,6 +33,26 @@ table! {
         fk_proposal -> Int4,
         fk_reviewer -> Int4,
         reviewed -> Bool,
+        abstained -> Bool,
+        active -> Bool,
+    }
+}
+
+table! {
+    fcp_state_transition (id) {
+        id -> Int4,
+        fk_proposal -> Int4,
+        from_state -> Nullable<Varchar>,
+        to_state -> Varchar,
+        transitioned_at -> Timestamp,
+    }
+}
+
+table! {
+    github_team_member (id) {
+        id -> Int4,
+        team -> Varchar,
+        fk_member -> Int4,
     }
 }
 
@@ -135,6 +160,8 @@ joinable!(fcp_proposal -> githubuser (fk_initiator));
 joinable!(fcp_proposal -> issue (fk_issue));
 joinable!(fcp_review_request -> fcp_proposal (fk_proposal));
 joinable!(fcp_review_request -> githubuser (fk_reviewer));
+joinable!(fcp_state_transition -> fcp_proposal (fk_proposal));
+joinable!(github_team_member -> githubuser (fk_member));
 joinable!(issue -> milestone (fk_milestone));
 joinable!(issuecomment -> issue (fk_issue));
 joinable!(issuecomment -> githubuser (fk_user));
@@ -20,13 +20,20 @@ pub enum DashError {
     R2d2Timeout(r2d2::GetTimeout),
     DieselError(diesel::result::Error),
     Template(handlebars::RenderError),
+    TemplateRender(handlebars::TemplateRenderError),
     Misc(Option<String>),
+    /// Why we won't do what someone asked of us, worded for them since it's posted to GitHub.
+    Rejected(String),
 }
 
 impl From<handlebars::RenderError> for DashError {
     fn from(e: handlebars::RenderError) -> Self { DashError::Template(e) }
 }
 
+impl From<handlebars::TemplateRenderError> for DashError {
+    fn from(e: handlebars::TemplateRenderError) -> Self { DashError::TemplateRender(e) }
+}
+
 impl From<hyper::error::Error> for DashError {
     fn from(e: hyper::error::Error) -> Self { DashError::Hyper(e) }
 }
@@ -17,8 +17,12 @@ use serde::de::DeserializeOwned;
 use serde_json;
 
 use config::CONFIG;
+use domain::github::GitHubUser;
 use error::{DashError, DashResult};
-use github::models::{CommentFromJson, IssueFromJson, PullRequestFromJson, PullRequestUrls};
+use github::models::{CheckRunsFromJson, CombinedStatusFromJson, CommentFromJson,
+                     IssueFromJson, MergeResultFromJson, PullRequestFromJson,
+                     PullRequestStatusFromJson, PullRequestUrls};
+use teams::MergeMethod;
 
 pub const BASE_URL: &'static str = "https://api.github.com";
 
@@ -84,6 +88,32 @@ impl Client {
         Ok(repos)
     }
 
+    /// The members of a GitHub team, given as `org/team-slug`.
+    pub fn team_members(&self, team: &str) -> DashResult<Vec<GitHubUser>> {
+        let mut parts = team.splitn(2, '/');
+        let (org, slug) = match (parts.next(), parts.next()) {
+            (Some(org), Some(slug)) => (org, slug),
+            _ => throw!(DashError::Misc(Some(format!("`{}` isn't a GitHub team", team)))),
+        };
+
+        self.get_models(&format!("{}/orgs/{}/teams/{}/members", BASE_URL, org, slug),
+            Some(&btreemap! {
+                "per_page" => format!("{}", PER_PAGE)
+            }))
+    }
+
+    /// Look a user up by login, `None` if there's no such user.
+    pub fn user(&self, login: &str) -> DashResult<Option<GitHubUser>> {
+        let url = format!("{}/users/{}", BASE_URL, login);
+        let mut res = self.get(&url, None)?;
+
+        match res.status {
+            StatusCode::Ok => Ok(Some(self.deserialize(&mut res)?)),
+            StatusCode::NotFound => Ok(None),
+            _ => throw!(DashError::Misc(Some(read_to_string(&mut res)?))),
+        }
+    }
+
     pub fn issues_since(&self, repo: &str, start: DateTime<Utc>) -> DashResult<Vec<IssueFromJson>> {
         self.get_models(&format!("{}/repos/{}/issues", BASE_URL, repo),
             Some(&btreemap! {
@@ -131,6 +161,81 @@ impl Client {
         }
     }
 
+    pub fn pull_request_status(&self,
+                               repo: &str,
+                               pr_num: i32)
+                               -> DashResult<PullRequestStatusFromJson> {
+        let url = format!("{}/repos/{}/pulls/{}", BASE_URL, repo, pr_num);
+        let mut res = self.get(&url, None)?;
+
+        if StatusCode::Ok != res.status {
+            throw!(DashError::Misc(Some(read_to_string(&mut res)?)))
+        }
+
+        self.deserialize(&mut res)
+    }
+
+    pub fn combined_status(&self, repo: &str, sha: &str) -> DashResult<CombinedStatusFromJson> {
+        let url = format!("{}/repos/{}/commits/{}/status", BASE_URL, repo, sha);
+        let mut res = self.get(&url, None)?;
+
+        if StatusCode::Ok != res.status {
+            throw!(DashError::Misc(Some(read_to_string(&mut res)?)))
+        }
+
+        self.deserialize(&mut res)
+    }
+
+    /// The check runs of a commit, up to the first `PER_PAGE` of them.
+    pub fn check_runs(&self, repo: &str, sha: &str) -> DashResult<CheckRunsFromJson> {
+        let url = format!("{}/repos/{}/commits/{}/check-runs?per_page={}",
+                          BASE_URL, repo, sha, PER_PAGE);
+        let mut res = self.set_headers(self.client.get(&url))
+            // the checks API is still a preview
+            .header(Accept("application/vnd.github.antiope-preview+json".to_string()))
+            .send()?;
+
+        if StatusCode::Ok != res.status {
+            throw!(DashError::Misc(Some(read_to_string(&mut res)?)))
+        }
+
+        self.deserialize(&mut res)
+    }
+
+    /// Merge a pull request, as long as its head is still at `sha`.
+    pub fn merge_pull_request(&self,
+                              repo: &str,
+                              pr_num: i32,
+                              sha: &str,
+                              method: MergeMethod)
+                              -> DashResult<()> {
+        let url = format!("{}/repos/{}/pulls/{}/merge", BASE_URL, repo, pr_num);
+        let payload = serde_json::to_string(&btreemap!(
+            "sha" => sha,
+            "merge_method" => method.repr()
+        ))?;
+        let mut res = self.put(&url, &payload)?;
+
+        if StatusCode::Ok != res.status {
+            // GitHub explains why, e.g. when the branch is protected or has conflicts
+            let body = read_to_string(&mut res)?;
+            let message = serde_json::from_str::<serde_json::Value>(&body)
+                .ok()
+                .and_then(|v| v.get("message").and_then(|m| m.as_str()).map(String::from));
+            match message {
+                Some(message) => throw!(DashError::Rejected(message)),
+                None => throw!(DashError::Misc(Some(body))),
+            }
+        }
+
+        let result: MergeResultFromJson = self.deserialize(&mut res)?;
+        if !result.merged {
+            throw!(DashError::Rejected(result.message))
+        }
+
+        Ok(())
+    }
+
     fn next_page(h: &Headers) -> Option<String> {
         if let Some(lh) = h.get::<Link>() {
             for link in (**lh).split(',').map(|s| s.trim()) {
@@ -205,6 +310,27 @@ impl Client {
         self.deserialize(&mut self.post(&url, &payload)?)
     }
 
+    pub fn new_issue(&self,
+                     repo: &str,
+                     title: &str,
+                     body: &str,
+                     assignee: &str)
+                     -> DashResult<IssueFromJson> {
+        let url = format!("{}/repos/{}/issues", BASE_URL, repo);
+        let payload = serde_json::to_string(&json!({
+            "title": title,
+            "body": body,
+            "assignees": [assignee],
+        }))?;
+        let mut res = self.post(&url, &payload)?;
+
+        if StatusCode::Created != res.status {
+            throw!(DashError::Misc(Some(read_to_string(&mut res)?)))
+        }
+
+        self.deserialize(&mut res)
+    }
+
     pub fn edit_comment(&self,
                         repo: &str,
                         comment_num: i32,
@@ -230,6 +356,10 @@ impl Client {
         self.set_headers(self.client.post(url).body(payload)).send()
     }
 
+    fn put(&self, url: &str, payload: &str) -> Result<Response, hyper::error::Error> {
+        self.set_headers(self.client.put(url).body(payload)).send()
+    }
+
     fn delete(&self, url: &str) -> Result<Response, hyper::error::Error> {
         self.set_headers(self.client.delete(url)).send()
     }
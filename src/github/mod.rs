@@ -4,6 +4,7 @@
 pub mod client;
 pub mod models;
 mod nag;
+pub mod templates;
 pub mod webhooks;
 
 use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
@@ -16,10 +17,13 @@ use DB_POOL;
 use domain::github::*;
 use domain::schema::*;
 use error::DashResult;
+use teams::SETUP;
 
 use self::client::Client;
 use self::models::{CommentFromJson, IssueFromJson, PullRequestFromJson};
 
+pub use self::nag::{evaluate_all_nags, reconcile_review_requests, Label};
+
 lazy_static! {
     pub static ref GH: Client = Client::new();
 }
@@ -59,6 +63,54 @@ pub fn record_successful_update(ingest_start: NaiveDateTime) -> DashResult<()> {
     Ok(())
 }
 
+/// Fetch and cache the members of the GitHub teams which teams in `mscbot.toml` take members
+/// from. Returns whether the members of any of them changed.
+///
+/// If a team can't be fetched, its previously fetched members are kept.
+pub fn refresh_team_members() -> DashResult<bool> {
+    use domain::schema::github_team_member::dsl::*;
+
+    let conn = &*DB_POOL.get()?;
+    let setup = SETUP.get();
+
+    let mut changed = false;
+    for github_team in setup.github_teams() {
+        info!("fetching the members of {}", github_team);
+        let members = ok_or_continue!(GH.team_members(github_team), why =>
+            error!("Unable to fetch the members of {}: {:?}", github_team, why));
+
+        for member in &members {
+            handle_user(conn, member)?;
+        }
+
+        let mut member_ids = members.iter().map(|member| member.id).collect::<Vec<_>>();
+        member_ids.sort();
+
+        let mut cached_ids = github_team_member
+            .select(fk_member)
+            .filter(team.eq(github_team))
+            .load::<i32>(conn)?;
+        cached_ids.sort();
+
+        if cached_ids == member_ids {
+            continue;
+        }
+
+        info!("the members of {} changed", github_team);
+        changed = true;
+
+        diesel::delete(github_team_member.filter(team.eq(github_team))).execute(conn)?;
+
+        let new_members = member_ids
+            .iter()
+            .map(|&member_id| NewGitHubTeamMember { team: github_team, fk_member: member_id })
+            .collect::<Vec<_>>();
+        diesel::insert(&new_members).into(github_team_member).execute(conn)?;
+    }
+
+    Ok(changed)
+}
+
 pub fn ingest_since(repo: &str, start: DateTime<Utc>) -> DashResult<()> {
     info!("fetching all {} issues and comments since {}", repo, start);
     let issues = GH.issues_since(repo, start)?;
@@ -134,14 +186,24 @@ pub fn handle_comment(conn: &PgConnection, comment: CommentFromJson, repo: &str)
     let comment: IssueComment = comment.with_repo(repo)?;
 
     // We only want to run `nag::update_nags` on insert to avoid
-    // double-processing commits, so we can't use upsert here
-    if issuecomment::table
-           .find(comment.id)
-           .get_result::<IssueComment>(conn)
-           .is_ok() {
+    // double-processing commits, so we can't use upsert here. Edits are
+    // diffed against the body we stored before so only changed commands apply.
+    let previous = issuecomment::table
+        .find(comment.id)
+        .get_result::<IssueComment>(conn)
+        .optional()?;
+
+    if let Some(previous) = previous {
         diesel::update(issuecomment::table.find(comment.id))
             .set(&comment)
             .execute(conn)?;
+
+        if previous.body != comment.body {
+            ok_or!(nag::update_nags_for_edit(&previous, &comment), why => {
+                error!("Problem updating FCPs for edited comment: {:?}", &why);
+                throw!(why);
+            });
+        }
     } else {
         diesel::insert(&comment)
             .into(issuecomment::table)
@@ -156,6 +218,28 @@ pub fn handle_comment(conn: &PgConnection, comment: CommentFromJson, repo: &str)
     Ok(())
 }
 
+pub fn handle_comment_deleted(conn: &PgConnection,
+                              comment: CommentFromJson,
+                              repo: &str)
+                              -> DashResult<()> {
+    let comment: IssueComment = comment.with_repo(repo)?;
+
+    // nothing to undo for comments we never saw
+    let stored = issuecomment::table
+        .find(comment.id)
+        .get_result::<IssueComment>(conn)
+        .optional()?;
+
+    if let Some(stored) = stored {
+        ok_or!(nag::handle_deleted_comment(&stored), why => {
+            error!("Problem updating FCPs for deleted comment: {:?}", &why);
+            throw!(why);
+        });
+    }
+
+    Ok(())
+}
+
 pub fn handle_issue(conn: &PgConnection, issue: IssueFromJson, repo: &str) -> DashResult<()> {
     // user handling
     handle_user(conn, &issue.user)?;
@@ -175,11 +259,26 @@ pub fn handle_issue(conn: &PgConnection, issue: IssueFromJson, repo: &str) -> Da
     }
 
     // handle issue itself
-    {
+    let (previous, issue) = {
         use domain::schema::issue::dsl::*;
-        diesel::insert(&i.on_conflict((repository, number), do_update().set(&i)))
+        let previous = issue
+            .filter(repository.eq(&i.repository))
+            .filter(number.eq(i.number))
+            .first::<Issue>(conn)
+            .optional()?;
+        let updated = diesel::insert(&i.on_conflict((repository, number), do_update().set(&i)))
             .into(issue)
-            .execute(conn)?;
+            .get_result::<Issue>(conn)?;
+        (previous, updated)
+    };
+
+    // team labels may have changed, which changes who has to review a pending proposal
+    // a new issue can't have a proposal yet
+    let changed = previous.map(|previous| previous.labels != issue.labels);
+    if changed.unwrap_or(false) {
+        ok_or!(nag::update_review_requests(&issue), why =>
+            error!("Problem updating review requests for {}#{}: {:?}",
+                   repo, issue.number, why));
     }
 
     Ok(())
@@ -192,6 +291,21 @@ pub fn handle_user(conn: &PgConnection, user: &GitHubUser) -> DashResult<()> {
     Ok(())
 }
 
+/// Every GitHub user we know about, e.g. to back them up with `mscbot-rs export-users`.
+pub fn all_users() -> DashResult<Vec<GitHubUser>> {
+    let conn = &*DB_POOL.get()?;
+    Ok(githubuser::table.order(githubuser::id).load::<GitHubUser>(conn)?)
+}
+
+/// Insert or update GitHub users, e.g. from a backup made with `mscbot-rs export-users`.
+pub fn import_users(users: &[GitHubUser]) -> DashResult<()> {
+    let conn = &*DB_POOL.get()?;
+    for user in users {
+        handle_user(conn, user)?;
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -190,3 +190,47 @@ impl PullRequestFromJson {
         }
     }
 }
+
+/// The parts of a pull request which decide whether we can merge it.
+#[derive(Debug, Deserialize)]
+pub struct PullRequestStatusFromJson {
+    pub state: String,
+    pub merged: bool,
+    /// `None` while GitHub is still working it out.
+    pub mergeable: Option<bool>,
+    pub head: CommitRefFromJson,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CommitRefFromJson {
+    pub sha: String,
+}
+
+/// The combined state of all CI statuses of a commit.
+#[derive(Debug, Deserialize)]
+pub struct CombinedStatusFromJson {
+    /// One of `success`, `pending`, `failure` or `error`.
+    pub state: String,
+    pub total_count: i32,
+}
+
+/// The check runs of a commit, e.g. those of GitHub Actions. They aren't part of its combined
+/// status.
+#[derive(Debug, Deserialize)]
+pub struct CheckRunsFromJson {
+    pub total_count: i32,
+    pub check_runs: Vec<CheckRunFromJson>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CheckRunFromJson {
+    pub name: String,
+    /// `None` until the check run has completed, then e.g. `success`, `failure` or `neutral`.
+    pub conclusion: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MergeResultFromJson {
+    pub merged: bool,
+    pub message: String,
+}
@@ -1,21 +1,23 @@
+use std::cmp;
 use std::collections::BTreeSet;
 use std::sync::Mutex;
 use std::fmt;
 
-use chrono::{Duration, Utc};
+use chrono::{Duration, NaiveDateTime, Utc};
 use diesel::prelude::*;
 use diesel;
 
-use config::MSC_BOT_MENTION;
 use DB_POOL;
 use domain::github::{GitHubUser, Issue, IssueComment};
-use domain::mscbot::{FcpConcern, FcpProposal, FcpReviewRequest, FeedbackRequest, NewFcpProposal,
-                     NewFcpConcern, NewFcpReviewRequest, NewFeedbackRequest};
+use domain::mscbot::{FcpConcern, FcpProposal, FcpReviewRequest, FcpState, FeedbackRequest,
+                     NewFcpProposal, NewFcpConcern, NewFcpReviewRequest, NewFcpStateTransition,
+                     NewFeedbackRequest};
 use domain::schema::*;
 use error::*;
 use github::models::CommentFromJson;
-use teams::SETUP;
+use teams::{QuorumPolicy, ReviewReset, SETUP};
 use super::GH;
+use super::templates;
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash)]
 pub enum Label {
@@ -30,7 +32,17 @@ pub enum Label {
 }
 
 impl Label {
-    fn as_str(self) -> &'static str {
+    pub const ALL: [Label; 8] = [Label::FFCP,
+                                 Label::PFCP,
+                                 Label::FCP,
+                                 Label::Postponed,
+                                 Label::Closed,
+                                 Label::DispositionMerge,
+                                 Label::DispositionClose,
+                                 Label::DispositionPostpone];
+
+    /// The name of the label unless `mscbot.toml` maps it to another one.
+    pub fn as_str(self) -> &'static str {
         use self::Label::*;
         match self {
             FFCP => "finished-final-comment-period",
@@ -52,12 +64,22 @@ impl fmt::Display for Label {
 }
 
 impl Issue {
+    /// The name of a label in this issue's repo, `None` if the repo doesn't use it.
+    fn label_name(&self, label: Label) -> Option<String> {
+        SETUP.get().label_name(&self.repository, label)
+    }
+
     fn remove_label(&self, label: Label) {
-        let _ = GH.remove_label(&self.repository, self.number, label.as_str());
+        if let Some(name) = self.label_name(label) {
+            let _ = GH.remove_label(&self.repository, self.number, &name);
+        }
     }
 
     fn add_label(&self, label: Label) -> DashResult<()> {
-        GH.add_label(&self.repository, self.number, label.as_str())
+        match self.label_name(label) {
+            Some(name) => GH.add_label(&self.repository, self.number, &name),
+            None => Ok(()),
+        }
     }
 
     fn close(&self) {
@@ -70,11 +92,280 @@ lazy_static! {
     static ref NAG_LOCK: Mutex<()> = Mutex::new(());
 }
 
-// TODO check if new subteam label added for existing proposals
-
 pub fn update_nags(comment: &IssueComment) -> DashResult<()> {
     let _in_progress_marker = NAG_LOCK.lock();
 
+    process_comment(comment, &comment.body, true, true)
+}
+
+/// Apply the changes made by editing a comment we've already processed.
+///
+/// Command lines which are new in the edit are processed like those of a new comment. Of the
+/// command lines which were removed, only concerns are reverted: a concern raised by the
+/// comment is withdrawn (unless it was already resolved), and a concern resolved by the
+/// comment is reopened. Other commands (proposals, reviews, abstentions, feedback requests)
+/// stand until they're explicitly undone with another command.
+pub fn update_nags_for_edit(previous: &IssueComment, comment: &IssueComment) -> DashResult<()> {
+    let _in_progress_marker = NAG_LOCK.lock();
+
+    let setup = SETUP.get();
+    let (added, removed) = diff_command_lines(&previous.body, &comment.body, setup.mentions());
+    if added.is_empty() && removed.is_empty() {
+        return Ok(());
+    }
+
+    debug!("comment {} was edited: {} command line(s) added, {} removed",
+           comment.id, added.len(), removed.len());
+
+    if !removed.is_empty() {
+        ok_or!(revert_removed_commands(comment, &removed), why =>
+            error!("Unable to revert commands removed from comment id {}: {:?}",
+                   comment.id, why));
+    }
+
+    // a comment which already had commands has already been answered, if it needed to be
+    let answered = MscbotCommand::parse_all(&previous.body, setup.mentions()).next().is_some();
+
+    process_comment(comment, &added.join("\n"), false, !answered)
+}
+
+/// Clean up after a comment was deleted on GitHub.
+///
+/// Concerns raised or resolved by the comment are reverted like they would be if the command
+/// lines had been edited out, and feedback it gave is requested again. If it was the tracking
+/// comment of an open proposal, a new tracking comment is posted. Our copy of the comment is
+/// then removed, unless it's part of an FCP's history (e.g. it started a proposal or raised a
+/// concern which has since been resolved).
+pub fn handle_deleted_comment(comment: &IssueComment) -> DashResult<()> {
+    let _in_progress_marker = NAG_LOCK.lock();
+
+    let conn = &*DB_POOL.get()?;
+
+    info!("comment id {} was deleted", comment.id);
+
+    let (_, removed) = diff_command_lines(&comment.body, "", SETUP.get().mentions());
+    if !removed.is_empty() {
+        ok_or!(revert_removed_commands(comment, &removed), why =>
+            error!("Unable to revert commands of deleted comment id {}: {:?}",
+                   comment.id, why));
+    }
+
+    let issue = issue::table.find(comment.fk_issue).first::<Issue>(conn)?;
+
+    let live_proposal = {
+        use diesel::pg::expression::dsl::any;
+        use domain::schema::fcp_proposal::dsl::*;
+        fcp_proposal
+            .filter(fk_issue.eq(issue.id))
+            .filter(state.eq(any(FcpState::live_reprs())))
+            .first::<FcpProposal>(conn)
+            .optional()?
+    };
+
+    let unanswered = {
+        use domain::schema::rfc_feedback_request::dsl::*;
+        diesel::update(rfc_feedback_request.filter(fk_feedback_comment.eq(comment.id)))
+            .set(fk_feedback_comment.eq(None::<i32>))
+            .execute(conn)?
+    };
+
+    if let Some(proposal) = live_proposal {
+        if proposal.fk_bot_tracking_comment == comment.id {
+            ok_or!(repost_status_comment(&issue, proposal), why =>
+                error!("Unable to repost tracking comment for {}#{}: {:?}",
+                       issue.repository, issue.number, why));
+        } else if unanswered > 0 {
+            update_status_comment(&issue, &proposal)?;
+        }
+    }
+
+    if is_comment_referenced(comment.id)? {
+        info!("keeping deleted comment id {}, it's part of an FCP's history", comment.id);
+    } else {
+        diesel::delete(issuecomment::table.find(comment.id)).execute(conn)?;
+    }
+
+    ok_or!(evaluate_nags(), why =>
+        error!("Unable to evaluate outstanding proposals: {:?}", why));
+
+    Ok(())
+}
+
+/// Post a fresh tracking comment for a proposal whose tracking comment was deleted.
+fn repost_status_comment(issue: &Issue, mut proposal: FcpProposal) -> DashResult<()> {
+    use domain::schema::fcp_proposal::dsl::*;
+
+    let conn = &*DB_POOL.get()?;
+
+    let initiator = githubuser::table
+        .find(proposal.fk_initiator)
+        .first::<GitHubUser>(conn)?;
+    let disp = FcpDisposition::from_str(&proposal.disposition)?;
+
+    let gh_comment = MscbotComment::new(issue,
+                                        CommentType::FcpProposed(&initiator,
+                                                                 disp,
+                                                                 &[],
+                                                                 &[],
+                                                                 &[],
+                                                                 &[]));
+    let gh_comment = gh_comment.post(None)?.with_repo(&issue.repository)?;
+
+    if let Err(why) = diesel::insert(&gh_comment).into(issuecomment::table).execute(conn) {
+        warn!("issue inserting new record, maybe received webhook for it: {:?}", why);
+    }
+
+    proposal.fk_bot_tracking_comment = gh_comment.id;
+    diesel::update(fcp_proposal.find(proposal.id))
+        .set(&proposal)
+        .execute(conn)?;
+
+    update_status_comment(issue, &proposal)
+}
+
+/// Whether anything in the FCP tables still points at a comment.
+fn is_comment_referenced(comment_id: i32) -> DashResult<bool> {
+    let conn = &*DB_POOL.get()?;
+
+    let proposals = {
+        use domain::schema::fcp_proposal::dsl::*;
+        fcp_proposal
+            .filter(fk_initiating_comment.eq(comment_id)
+                .or(fk_bot_tracking_comment.eq(comment_id))
+                .or(fk_cancelling_comment.eq(comment_id)))
+            .count()
+            .get_result::<i64>(conn)?
+    };
+
+    let concerns = {
+        use domain::schema::fcp_concern::dsl::*;
+        fcp_concern
+            .filter(fk_initiating_comment.eq(comment_id)
+                .or(fk_resolved_comment.eq(comment_id)))
+            .count()
+            .get_result::<i64>(conn)?
+    };
+
+    let feedback = {
+        use domain::schema::rfc_feedback_request::dsl::*;
+        rfc_feedback_request
+            .filter(fk_feedback_comment.eq(comment_id))
+            .count()
+            .get_result::<i64>(conn)?
+    };
+
+    Ok(proposals + concerns + feedback > 0)
+}
+
+/// Split the command lines of an edited comment into those which were added and those which
+/// were removed.
+fn diff_command_lines<'a>(previous: &'a str,
+                          current: &'a str,
+                          mentions: &[String])
+                          -> (Vec<&'a str>, Vec<&'a str>) {
+    let command_lines = |body: &'a str| {
+        body.lines()
+            .map(str::trim)
+            .filter(|l| strip_mention(l, mentions).is_some())
+            .collect::<Vec<_>>()
+    };
+
+    let previous = command_lines(previous);
+    let current = command_lines(current);
+
+    let added = current.iter().filter(|l| !previous.contains(l)).cloned().collect();
+    let removed = previous.iter().filter(|l| !current.contains(l)).cloned().collect();
+
+    (added, removed)
+}
+
+/// Undo the concerns raised or resolved by command lines which were edited out of a comment.
+fn revert_removed_commands(comment: &IssueComment, removed: &[&str]) -> DashResult<()> {
+    use domain::schema::fcp_concern::dsl::*;
+
+    let conn = &*DB_POOL.get()?;
+
+    let issue = issue::table.find(comment.fk_issue).first::<Issue>(conn)?;
+
+    let proposal = {
+        use diesel::pg::expression::dsl::any;
+        use domain::schema::fcp_proposal::dsl::*;
+        fcp_proposal
+            .filter(fk_issue.eq(issue.id))
+            .filter(state.eq(any(FcpState::live_reprs())))
+            .first::<FcpProposal>(conn)
+            .optional()?
+    };
+    let mut proposal = match proposal {
+        Some(proposal) => proposal,
+        None => return Ok(()),
+    };
+
+    let mut changed = false;
+    let mut reopened = false;
+    let setup = SETUP.get();
+    let commands = removed.iter()
+                          .filter_map(|l| strip_mention(l, setup.mentions()))
+                          .filter_map(|invocation| MscbotCommand::from_invocation(invocation).ok());
+    for command in commands {
+        match command {
+            MscbotCommand::NewConcern(concern_name) => {
+                let withdrawn = diesel::delete(fcp_concern
+                        .filter(fk_proposal.eq(proposal.id))
+                        .filter(fk_initiating_comment.eq(comment.id))
+                        .filter(fk_resolved_comment.is_null())
+                        .filter(name.eq(concern_name)))
+                    .execute(conn)?;
+
+                if withdrawn > 0 {
+                    info!("withdrew concern `{}` on {}#{}: removed from comment id {}",
+                          concern_name, issue.repository, issue.number, comment.id);
+                    changed = true;
+                }
+            }
+            MscbotCommand::ResolveConcern(concern_name) => {
+                let unresolved = diesel::update(fcp_concern
+                        .filter(fk_proposal.eq(proposal.id))
+                        .filter(fk_resolved_comment.eq(comment.id))
+                        .filter(name.eq(concern_name)))
+                    .set(fk_resolved_comment.eq(None::<i32>))
+                    .execute(conn)?;
+
+                if unresolved > 0 {
+                    info!("reopened concern `{}` on {}#{}: removed from comment id {}",
+                          concern_name, issue.repository, issue.number, comment.id);
+                    changed = true;
+                    reopened = true;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    // an open concern blocks FCP, just like a newly raised one
+    if reopened && proposal.fcp_state()? == FcpState::InFcp {
+        transition(&mut proposal, FcpState::Proposed)?;
+
+        let _ = issue.add_label(Label::PFCP);
+        issue.remove_label(Label::FCP);
+    }
+
+    if changed {
+        update_status_comment(&issue, &proposal)?;
+    }
+
+    Ok(())
+}
+
+/// Process the command lines in `body`, which belong to `comment`. Comments without commands
+/// answer any feedback requested from their author if `resolve_feedback` is set. Commands we
+/// can't act on are explained in a single reply to the comment, if `reply` is set.
+fn process_comment(
+    comment: &IssueComment,
+    body: &str,
+    resolve_feedback: bool,
+    reply: bool,
+) -> DashResult<()> {
     let conn = &*DB_POOL.get()?;
 
     let issue = issue::table.find(comment.fk_issue).first::<Issue>(conn)?;
@@ -86,33 +377,68 @@ pub fn update_nags(comment: &IssueComment) -> DashResult<()> {
     let subteam_members = subteam_members(&issue)?;
 
     // Attempt to parse all commands out of the comment
-    let mut any = false;
-    for command in MscbotCommand::from_str_all(&comment.body) {
-        any = true;
-
+    let setup = SETUP.get();
+    let commands = MscbotCommand::parse_all(body, setup.mentions()).collect::<Vec<_>>();
+
+    if commands.is_empty() {
+        if resolve_feedback {
+            ok_or!(resolve_applicable_feedback_requests(&author, &issue, comment),
+                why => error!("Unable to resolve feedback requests for comment id {}: {:?}",
+                            comment.id, why));
+        }
+    } else if subteam_members.iter().find(|&u| u == &author).is_none() {
         // Don't accept bot commands from non-subteam members.
-        // Early return because we'll just get here again...
-        if subteam_members.iter().find(|&u| u == &author).is_none() {
-            info!("command author ({}) doesn't appear in any relevant subteams",
-                  author.login);
-            return Ok(());
+        info!("command author ({}) doesn't appear in any relevant subteams",
+              author.login);
+
+        if reply {
+            let reason = if subteam_members.is_empty() {
+                let labels = setup.team_labels()
+                    .map(|label| format!("`{}`", label.0))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!("I can't accept commands here because no team is tagged on this \
+                         issue. Please add one of these labels first: {}", labels)
+            } else {
+                "I can't accept commands from you here because you are not on any team tagged \
+                 on this issue.".to_string()
+            };
+            reject_command(&issue, &author, &reason);
         }
 
+        return Ok(());
+    }
+
+    // the reasons for the commands we couldn't act on, answered all at once
+    let mut rejections = Vec::new();
+
+    for (line, command) in commands {
+        let command = ok_or_continue!(command, why => {
+            info!("Unable to parse command `{}` in comment id {}: {:?}",
+                  line, comment.id, why);
+            if let DashError::Rejected(reason) = why {
+                rejections.push(format!("I couldn't process `{}`: {}", line.trim(), reason));
+            }
+        });
+
         debug!("processing mscbot command: {:?}", &command);
         let process = command.process(&author, &issue, comment, &subteam_members);
         ok_or!(process, why => {
             error!("Unable to process command for comment id {}: {:?}",
                 comment.id, why);
-            return Ok(());
+            // e.g. the command would move the proposal into a state it can't reach anymore
+            if let DashError::Rejected(reason) = why {
+                rejections.push(format!("I couldn't process `{}`: {}", line.trim(), reason));
+                continue;
+            }
+            break;
         });
 
         debug!("mscbot command is processed");
     }
 
-    if !any {
-        ok_or!(resolve_applicable_feedback_requests(&author, &issue, comment),
-            why => error!("Unable to resolve feedback requests for comment id {}: {:?}",
-                        comment.id, why));
+    if reply && !rejections.is_empty() {
+        reject_command(&issue, &author, &rejections.join("\n\n"));
     }
 
     ok_or!(evaluate_nags(), why =>
@@ -121,6 +447,160 @@ pub fn update_nags(comment: &IssueComment) -> DashResult<()> {
     Ok(())
 }
 
+/// Bring the review requests of an issue's pending proposal in line with the teams currently
+/// tagged on the issue, and rewrite the tracking comment if anything changed.
+pub fn update_review_requests(issue: &Issue) -> DashResult<()> {
+    let _in_progress_marker = NAG_LOCK.lock();
+
+    let conn = &*DB_POOL.get()?;
+
+    let pending_proposal = {
+        use domain::schema::fcp_proposal::dsl::*;
+
+        fcp_proposal
+            .filter(fk_issue.eq(issue.id))
+            .filter(state.eq(FcpState::Proposed.repr()))
+            .first::<FcpProposal>(conn)
+            .optional()?
+    };
+
+    if let Some(proposal) = pending_proposal {
+        let changes = sync_review_requests(issue, &proposal)?;
+
+        if !changes.is_empty() {
+            info!("review requests for {}#{} changed with its team labels: {:?}",
+                  issue.repository, issue.number, changes);
+
+            ok_or!(evaluate_nags(), why =>
+                error!("Unable to evaluate outstanding proposals: {:?}", why));
+        }
+    }
+
+    Ok(())
+}
+
+/// Reconcile the review requests of every pending proposal with the current team membership,
+/// e.g. after people joined or left a team in `mscbot.toml`.
+pub fn reconcile_review_requests() -> DashResult<()> {
+    use domain::schema::fcp_proposal::dsl::*;
+
+    let _in_progress_marker = NAG_LOCK.lock();
+
+    let conn = &*DB_POOL.get()?;
+
+    let pending_proposals = fcp_proposal
+        .filter(state.eq(FcpState::Proposed.repr()))
+        .load::<FcpProposal>(conn)?;
+    let num_pending = pending_proposals.len();
+    let (mut num_changed, mut num_added, mut num_removed) = (0, 0, 0);
+
+    for proposal in pending_proposals {
+        let issue = issue::table.find(proposal.fk_issue).first::<Issue>(conn);
+        let issue = ok_or_continue!(issue, why =>
+            error!("Unable to retrieve issue for proposal {}: {:?}",
+                    proposal.id, why));
+
+        let changes = ok_or_continue!(sync_review_requests(&issue, &proposal), why =>
+            error!("Unable to reconcile review requests for proposal {}: {:?}",
+                    proposal.id, why));
+
+        if !changes.is_empty() {
+            info!("reconciled review requests for {}#{} with team membership: \
+                   requested {:?}, dropped {:?}",
+                  issue.repository, issue.number, changes.added, changes.removed);
+
+            num_changed += 1;
+            num_added += changes.added.len();
+            num_removed += changes.removed.len();
+        }
+    }
+
+    info!("reconciled {} pending proposals with team membership: {} changed, \
+           {} review requests added, {} dropped",
+          num_pending, num_changed, num_added, num_removed);
+
+    if num_changed > 0 {
+        ok_or!(evaluate_nags(), why =>
+            error!("Unable to evaluate outstanding proposals: {:?}", why));
+    }
+
+    Ok(())
+}
+
+/// The reviewers added to and removed from a proposal by `sync_review_requests`.
+#[derive(Debug, Default)]
+struct ReviewerChanges {
+    added: Vec<String>,
+    removed: Vec<String>,
+}
+
+impl ReviewerChanges {
+    fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Add and remove review requests so that they match the members of the teams tagged on the
+/// issue. Reviews by members who stay on are kept as they are.
+///
+/// The requests of members who are dropped are only marked inactive, so that they get their
+/// review (or abstention) back if they're tagged again, e.g. when a label is put back.
+fn sync_review_requests(issue: &Issue, proposal: &FcpProposal) -> DashResult<ReviewerChanges> {
+    use domain::schema::fcp_review_request::dsl::*;
+
+    let conn = &*DB_POOL.get()?;
+
+    let members = subteam_members(issue)?;
+    let existing = fcp_review_request
+        .filter(fk_proposal.eq(proposal.id))
+        .load::<FcpReviewRequest>(conn)?;
+
+    let mut changes = ReviewerChanges::default();
+
+    // request reviews from members of newly tagged teams, or bring back their old request
+    let mut new_requests = Vec::new();
+    for member in &members {
+        match existing.iter().find(|request| request.fk_reviewer == member.id) {
+            Some(request) if request.active => continue,
+            Some(request) => {
+                diesel::update(fcp_review_request.find(request.id))
+                    .set(active.eq(true))
+                    .execute(conn)?;
+            }
+            None => {
+                new_requests.push(NewFcpReviewRequest {
+                    fk_proposal: proposal.id,
+                    fk_reviewer: member.id,
+                    reviewed: false,
+                    abstained: false,
+                    active: true,
+                });
+            }
+        }
+        changes.added.push(member.login.clone());
+    }
+
+    if !new_requests.is_empty() {
+        diesel::insert(&new_requests)
+            .into(fcp_review_request)
+            .execute(conn)?;
+    }
+
+    // and set aside the requests of anyone who isn't on a tagged team anymore
+    for request in existing.iter().filter(|request| request.active) {
+        if members.iter().all(|member| member.id != request.fk_reviewer) {
+            diesel::update(fcp_review_request.find(request.id))
+                .set(active.eq(false))
+                .execute(conn)?;
+
+            let reviewer = githubuser::table.find(request.fk_reviewer).first::<GitHubUser>(conn)?;
+            changes.removed.push(reviewer.login);
+        }
+    }
+
+    Ok(changes)
+}
+
 fn update_proposal_review_status(proposal_id: i32) -> DashResult<()> {
     let conn = &*DB_POOL.get()?;
     // this is an updated comment from the bot itself
@@ -130,7 +610,7 @@ fn update_proposal_review_status(proposal_id: i32) -> DashResult<()> {
     let proposal: FcpProposal = fcp_proposal::table.find(proposal_id).first(conn)?;
 
     // don't update any statuses if the fcp is running or closed
-    if proposal.fcp_start.is_some() || proposal.fcp_closed {
+    if proposal.fcp_state()? != FcpState::Proposed {
         return Ok(());
     }
 
@@ -139,52 +619,63 @@ fn update_proposal_review_status(proposal_id: i32) -> DashResult<()> {
         .first(conn)?;
 
     // parse the status comment and mark any new reviews as reviewed
-    let reviewed = comment
-        .body
-        .lines()
-        .filter_map(|line| if line.starts_with("* [") {
-                        let l = line.trim_left_matches("* [");
-                        let reviewed = l.starts_with('x');
-                        let remaining = l.trim_left_matches("x] @").trim_left_matches(" ] @");
-
-                        if let Some(username) = remaining.split_whitespace().next() {
-                            trace!("reviewer parsed as reviewed? {} (line: \"{}\")",
-                                   reviewed,
-                                   l);
-
-                            if reviewed { Some(username) } else { None }
-                        } else {
-                            warn!("An empty usename showed up in comment {} for proposal {}",
-                                  comment.id,
-                                  proposal.id);
-                            None
-                        }
-                    } else {
-                        None
-                    });
-
-    for username in reviewed {
+    for username in ticked_reviewers(&comment.body) {
         let user: GitHubUser = githubuser::table
             .filter(githubuser::login.eq(username))
             .first(conn)?;
 
         {
             use domain::schema::fcp_review_request::dsl::*;
-            let mut review_request: FcpReviewRequest = fcp_review_request
+            let review_request = fcp_review_request
                 .filter(fk_proposal.eq(proposal.id))
                 .filter(fk_reviewer.eq(user.id))
-                .first(conn)?;
-
-            review_request.reviewed = true;
-            diesel::update(fcp_review_request.find(review_request.id))
-                .set(&review_request)
-                .execute(conn)?;
+                .first::<FcpReviewRequest>(conn)
+                .optional()?;
+
+            // the reviewer may have been dropped since the comment was last rendered
+            let mut review_request = match review_request {
+                Some(r) => r,
+                None => continue,
+            };
+
+            if mark_reviewed(&mut review_request) {
+                diesel::update(fcp_review_request.find(review_request.id))
+                    .set(&review_request)
+                    .execute(conn)?;
+            }
         }
     }
 
     Ok(())
 }
 
+/// The logins whose boxes are ticked in a tracking comment. Only the reviewer checklist
+/// counts, which comes before the concerns and feedback requests.
+fn ticked_reviewers(body: &str) -> Vec<&str> {
+    body.lines()
+        .take_while(|line| !line.starts_with(NO_CONCERNS) && !line.starts_with(CONCERNS))
+        .filter(|line| line.starts_with("* [x] @"))
+        .filter_map(|line| line.trim_left_matches("* [x] @").split_whitespace().next())
+        .collect()
+}
+
+/// Record a review, which replaces an abstention. Returns whether anything changed.
+fn mark_reviewed(review_request: &mut FcpReviewRequest) -> bool {
+    let changed = !review_request.reviewed || review_request.abstained;
+
+    review_request.reviewed = true;
+    review_request.abstained = false;
+
+    changed
+}
+
+/// Check every pending and running proposal, e.g. to finish FCPs whose period has elapsed.
+pub fn evaluate_all_nags() -> DashResult<()> {
+    let _in_progress_marker = NAG_LOCK.lock();
+
+    evaluate_nags()
+}
+
 fn evaluate_nags() -> DashResult<()> {
     use diesel::prelude::*;
     use domain::schema::fcp_proposal::dsl::*;
@@ -193,7 +684,8 @@ fn evaluate_nags() -> DashResult<()> {
     let conn = &*DB_POOL.get()?;
 
     // first process all "pending" proposals (unreviewed or remaining concerns)
-    let pending = fcp_proposal.filter(fcp_start.is_null()).load::<FcpProposal>(conn);
+    let pending = fcp_proposal.filter(state.eq(FcpState::Proposed.repr()))
+                              .load::<FcpProposal>(conn);
     let pending_proposals = ok_or!(pending, why => {
         error!("Unable to retrieve list of pending proposals: {:?}", why);
         throw!(why)
@@ -214,9 +706,10 @@ fn evaluate_nags() -> DashResult<()> {
         // if the issue has been closed before an FCP starts,
         // then we just need to cancel the FCP entirely
         if !issue.open {
-            ok_or_continue!(cancel_fcp(&initiator, &issue, &proposal), why =>
+            ok_or_continue!(cancel_fcp(None, None, &issue, &proposal), why =>
                 error!("Unable to cancel FCP for proposal {}: {:?}",
                         proposal.id, why));
+            continue;
         }
 
         // check to see if any checkboxes were modified before we end up replacing the comment
@@ -233,19 +726,33 @@ fn evaluate_nags() -> DashResult<()> {
             why => error!("Unable to retrieve concerns for proposal {}: {:?}",
                     proposal.id, why));
 
-        let num_outstanding_reviews = reviews.iter().filter(|&&(_, ref r)| !r.reviewed).count();
-        let num_complete_reviews = reviews.len() - num_outstanding_reviews;
+        let feedback = ok_or_continue!(list_feedback_requests(issue.id),
+            why => error!("Unable to retrieve feedback requests for proposal {}: {:?}",
+                    proposal.id, why));
+
+        let teams = ok_or_continue!(tagged_teams(&issue), why =>
+            error!("Unable to retrieve the teams tagged on {}#{}: {:?}",
+                   issue.repository, issue.number, why));
+
+        // abstaining reviewers don't count towards the quorum in either direction
+        let num_outstanding_reviews = reviews
+            .iter()
+            .filter(|&&(_, ref r)| !r.reviewed && !r.abstained)
+            .count();
+        let num_complete_reviews = reviews.iter().filter(|&&(_, ref r)| r.reviewed).count();
         let num_active_concerns = concerns
             .iter()
             .filter(|&&(_, ref c)| c.fk_resolved_comment.is_none())
             .count();
 
-        // update existing status comment with reviews & concerns
+        // update existing status comment with reviews, concerns & feedback requests
         let status_comment = MscbotComment::new(&issue, CommentType::FcpProposed(
                     &initiator,
                     FcpDisposition::from_str(&proposal.disposition)?,
+                    &teams,
                     &reviews,
-                    &concerns));
+                    &concerns,
+                    &feedback));
 
         let previous_comment: IssueComment = issuecomment
             .filter(issuecomment_id.eq(proposal.fk_bot_tracking_comment))
@@ -261,17 +768,23 @@ fn evaluate_nags() -> DashResult<()> {
                         proposal.id, why));
         }
 
-        let majority_complete = num_outstanding_reviews < num_complete_reviews;
+        let setup = SETUP.get();
+        let quorum_reached = if setup.per_team_quorum(&issue.repository) && !teams.is_empty() {
+            // a large team can't outvote a small one
+            teams.iter().all(|team| team.quorum_met(&reviews))
+        } else {
+            setup.quorum_policies(&issue.repository, &issue.labels)
+                .into_iter()
+                .all(|policy| policy.is_met(num_complete_reviews, num_outstanding_reviews))
+        };
+        let team_rules_met = teams.iter().all(|team| team.rules_met(&reviews));
 
-        if num_active_concerns == 0 && majority_complete && num_outstanding_reviews < 3 {
+        if num_active_concerns == 0 && quorum_reached && team_rules_met {
             // TODO only record the fcp as started if we know that we successfully commented
             // i.e. either the comment claims to have posted, or we get a comment back to reconcile
 
             // FCP can start now -- update the database
-            proposal.fcp_start = Some(Utc::now().naive_utc());
-            let update = diesel::update(fcp_proposal.find(proposal.id))
-                          .set(&proposal).execute(conn);
-            ok_or_continue!(update, why =>
+            let start = ok_or_continue!(transition(&mut proposal, FcpState::InFcp), why =>
                 error!("Unable to mark FCP {} as started: {:?}",
                        proposal.id, why));
 
@@ -292,10 +805,14 @@ fn evaluate_nags() -> DashResult<()> {
                     }
                 };
 
+                // fixed by the transition
+                let fcp_end = proposal.fcp_end.unwrap_or(start);
                 let comment_type = CommentType::FcpAllReviewedNoConcerns {
                     added_label: added_label,
                     author: &initiator,
                     status_comment_id: proposal.fk_bot_tracking_comment,
+                    duration: fcp_end - start,
+                    fcp_end: fcp_end,
                 };
 
                 // leave a comment for FCP start
@@ -307,18 +824,22 @@ fn evaluate_nags() -> DashResult<()> {
         }
     }
 
-    // look for any FCP proposals that entered FCP a week or more ago but aren't marked as closed
-    let one_business_week_ago = Utc::now().naive_utc() - Duration::days(10);
-    let ffcps = fcp_proposal.filter(fcp_start.le(one_business_week_ago))
-                            .filter(fcp_closed.eq(false))
-                            .load::<FcpProposal>(conn);
-    let finished_fcps = ok_or!(ffcps, why => {
-        error!("Unable to retrieve FCPs that need to be marked as finished: {:?}",
-               why);
+    // retry the merges of pull requests which weren't ready to be merged when their FCP
+    // finished, e.g. because their checks were still running
+    let unmerged = fcp_proposal.filter(state.eq(FcpState::Finished.repr()))
+                               .filter(merge_pending.eq(true))
+                               .load::<FcpProposal>(conn);
+    let unmerged = ok_or!(unmerged, why => {
+        error!("Unable to retrieve finished FCPs that still need to be merged: {:?}", why);
         throw!(why);
     });
 
-    for mut proposal in finished_fcps {
+    for mut proposal in unmerged {
+        let issue = issue::table.find(proposal.fk_issue).first::<Issue>(conn);
+        let issue = ok_or_continue!(issue, why =>
+            error!("Unable to find issue to match proposal {}: {:?}",
+                   proposal.id, why));
+
         let initiator = githubuser::table.find(proposal.fk_initiator)
                                          .first::<GitHubUser>(conn);
         let initiator = ok_or_continue!(initiator, why =>
@@ -326,18 +847,76 @@ fn evaluate_nags() -> DashResult<()> {
                     proposal.id,
                     why));
 
+        let outcome = execute_ffcp_actions(&issue, FcpDisposition::Merge);
+        if outcome == FfcpOutcome::MergePending {
+            debug!("{}#{} isn't ready to be merged yet", issue.repository, issue.number);
+            continue;
+        }
+
+        // whatever happened, there's nothing left to retry
+        ok_or_continue!(set_merge_pending(&mut proposal, false), why =>
+            error!("Unable to stop retrying to merge proposal {}: {:?}", proposal.id, why));
+
+        match outcome {
+            FfcpOutcome::Done => {
+                ok_or!(transition(&mut proposal, FcpState::Resolved), why =>
+                    error!("Unable to mark FCP {} as resolved: {:?}", proposal.id, why));
+                // the new issue links back to this one, so there's no need to announce it
+                ok_or!(open_follow_up_issue(&issue, &initiator), why =>
+                    error!("Unable to open follow-up issue for proposal {}: {:?}",
+                           proposal.id, why));
+            }
+            FfcpOutcome::MergeFailed(ref reason) => {
+                let comment = MscbotComment::new(&issue, CommentType::MergeFailed {
+                    author: &initiator,
+                    reason: reason,
+                });
+                ok_or!(comment.post(None), why =>
+                    error!("Unable to explain why proposal {} wasn't merged: {:?}",
+                           proposal.id, why));
+            }
+            // e.g. the repo stopped merging finished FCPs in the meantime
+            _ => (),
+        }
+    }
+
+    // closed -- older FCPs may not have their end recorded, so we have to check each one
+    // closed -- the duration depends on the repo and teams, so we have to check each one
+    let now = Utc::now().naive_utc();
+    let ffcps = fcp_proposal.filter(state.eq(FcpState::InFcp.repr()))
+                            .load::<FcpProposal>(conn);
+    let running_fcps = ok_or!(ffcps, why => {
+        error!("Unable to retrieve FCPs that need to be marked as finished: {:?}",
+               why);
+        throw!(why);
+    });
+
+    for mut proposal in running_fcps {
         let issue = issue::table.find(proposal.fk_issue).first::<Issue>(conn);
         let issue = ok_or_continue!(issue, why =>
             error!("Unable to find issue to match proposal {}: {:?}",
                    proposal.id, why));
 
+        // FCPs which started before we recorded their end take it from the current labels
+        let fcp_end = proposal.fcp_end.or_else(|| {
+            let duration = SETUP.get().fcp_duration(&issue.repository, &issue.labels);
+            proposal.fcp_start.map(|start| start + duration)
+        });
+        if !fcp_has_elapsed(fcp_end, now) {
+            continue;
+        }
+
+        let initiator = githubuser::table.find(proposal.fk_initiator)
+                                         .first::<GitHubUser>(conn);
+        let initiator = ok_or_continue!(initiator, why =>
+            error!("Unable to retrieve proposal initiator for proposal id {}: {:?}",
+                    proposal.id,
+                    why));
+
         // TODO only update the db if the comment posts, but reconcile if we find out it worked
 
         // update the fcp
-        proposal.fcp_closed = true;
-        let update_fcp = diesel::update(fcp_proposal.find(proposal.id))
-                                .set(&proposal).execute(conn);
-        ok_or_continue!(update_fcp, why =>
+        ok_or_continue!(transition(&mut proposal, FcpState::Finished), why =>
             error!("Unable to update FCP {}: {:?}", proposal.id, why));
 
         // parse the disposition:
@@ -357,12 +936,41 @@ fn evaluate_nags() -> DashResult<()> {
             }
         };
 
+        // carry out the disposition first, so that the comment can tell how that went
+        let outcome = execute_ffcp_actions(&issue, disp);
+        match outcome {
+            FfcpOutcome::Done => {
+                ok_or!(transition(&mut proposal, FcpState::Resolved), why =>
+                    error!("Unable to mark FCP {} as resolved: {:?}", proposal.id, why));
+            }
+            FfcpOutcome::MergePending => {
+                ok_or!(set_merge_pending(&mut proposal, true), why =>
+                    error!("Unable to schedule merging proposal {} again: {:?}",
+                           proposal.id, why));
+            }
+            _ => (),
+        }
+
+        // the follow-up waits for the merge, unless we aren't the ones merging
+        let follow_up = match outcome {
+            FfcpOutcome::Done | FfcpOutcome::NotAllowed if disp == FcpDisposition::Merge => {
+                ok_or!(open_follow_up_issue(&issue, &initiator), why => {
+                    error!("Unable to open follow-up issue for proposal {}: {:?}",
+                           proposal.id, why);
+                    None
+                })
+            }
+            _ => None,
+        };
+
         // Build the comment:
         let comment_type = CommentType::FcpWeekPassed {
             added_label,
             author: &initiator,
             status_comment_id: proposal.fk_bot_tracking_comment,
-            disposition: disp
+            disposition: disp,
+            outcome: &outcome,
+            follow_up: follow_up.as_ref().map(|&(ref repo, number)| (repo.as_str(), number)),
         };
         let fcp_close_comment = MscbotComment::new(&issue, comment_type);
 
@@ -371,37 +979,263 @@ fn evaluate_nags() -> DashResult<()> {
             error!("Unable to post FCP-ending comment for proposal {}: {:?}",
                     proposal.id, why));
 
-        execute_ffcp_actions(&issue, disp);
+        if let FfcpOutcome::MergeFailed(ref reason) = outcome {
+            let comment = MscbotComment::new(&issue, CommentType::MergeFailed {
+                author: &initiator,
+                reason: reason,
+            });
+            ok_or!(comment.post(None), why =>
+                error!("Unable to explain why proposal {} wasn't merged: {:?}",
+                       proposal.id, why));
+        }
     }
 
     Ok(())
 }
 
+/// Remember whether merging a finished proposal's pull request has to be retried.
+fn set_merge_pending(proposal: &mut FcpProposal, pending: bool) -> DashResult<()> {
+    use domain::schema::fcp_proposal;
+
+    let conn = &*DB_POOL.get()?;
+
+    proposal.merge_pending = pending;
+    diesel::update(fcp_proposal::table.find(proposal.id))
+        .set(&*proposal)
+        .execute(conn)?;
+
+    Ok(())
+}
+
+/// Move a proposal to a new state and record when that happened, keeping the timestamps of
+/// the proposal in step. Returns the time of the transition.
+///
+/// Illegal moves (e.g. sending a finished FCP back for review) are rejected with an
+/// explanation which can be shown to whoever asked for them.
+fn transition(proposal: &mut FcpProposal, to: FcpState) -> DashResult<NaiveDateTime> {
+    use domain::schema::fcp_proposal;
+
+    let conn = &*DB_POOL.get()?;
+
+    let from = proposal.fcp_state()?;
+    from.ensure_transition_to(to)?;
+
+    let now = Utc::now().naive_utc();
+    if from == to {
+        return Ok(now);
+    }
+
+    // the end of an FCP is announced when it starts, so later changes to the labels or the
+    // configuration mustn't move it
+    let duration = if to == FcpState::InFcp {
+        let issue = issue::table.find(proposal.fk_issue).first::<Issue>(conn)?;
+        Some(SETUP.get().fcp_duration(&issue.repository, &issue.labels))
+    } else {
+        None
+    };
+
+    proposal.state = to.repr().to_string();
+    match to {
+        FcpState::Proposed => {
+            proposal.fcp_start = None;
+            proposal.fcp_end = None;
+        }
+        FcpState::InFcp => {
+            proposal.fcp_start = Some(now);
+            proposal.fcp_end = duration.map(|duration| now + duration);
+        }
+        FcpState::Cancelled => proposal.cancelled_at = Some(now),
+        FcpState::Finished | FcpState::Resolved => (),
+    }
+
+    diesel::update(fcp_proposal::table.find(proposal.id))
+        .set(&*proposal)
+        .execute(conn)?;
+
+    record_transition(proposal.id, Some(from), to, now)?;
+
+    debug!("proposal {} went from {:?} to {:?}", proposal.id, from, to);
+
+    Ok(now)
+}
+
+fn record_transition(proposal_id: i32,
+                     from: Option<FcpState>,
+                     to: FcpState,
+                     at: NaiveDateTime)
+                     -> DashResult<()> {
+    use domain::schema::fcp_state_transition;
+
+    let conn = &*DB_POOL.get()?;
+
+    let transition = NewFcpStateTransition {
+        fk_proposal: proposal_id,
+        from_state: from.map(FcpState::repr),
+        to_state: to.repr(),
+        transitioned_at: at,
+    };
+
+    diesel::insert(&transition)
+        .into(fcp_state_transition::table)
+        .execute(conn)?;
+
+    Ok(())
+}
+
+/// Has a final comment period that ends at `fcp_end` run its course by `now`?
+fn fcp_has_elapsed(fcp_end: Option<NaiveDateTime>, now: NaiveDateTime) -> bool {
+    fcp_end.map(|end| end <= now).unwrap_or(false)
+}
+
+/// Open the follow-up issue the repo asks for after a merge FCP, assigned to the proposal's
+/// initiator. Returns the repository and number of the new issue.
+fn open_follow_up_issue(issue: &Issue, initiator: &GitHubUser)
+                        -> DashResult<Option<(String, i32)>> {
+    use config::CONFIG;
+
+    let setup = SETUP.get();
+    let follow_up = match setup.follow_up_issue(&issue.repository) {
+        Some(follow_up) => follow_up,
+        None => return Ok(None),
+    };
+
+    let (title, body) = follow_up.render(&issue.repository,
+                                         issue.number,
+                                         &issue.title,
+                                         &initiator.login)?;
+    let repo = follow_up.repository(&issue.repository);
+
+    if !CONFIG.post_comments {
+        info!("Skipping follow-up issue \"{}\" in {}, posts are disabled.", title, repo);
+        return Ok(None);
+    }
+
+    let created = GH.new_issue(repo, &title, &body, &initiator.login)?;
+    info!("Opened follow-up issue {}#{} for {}#{}",
+          repo, created.number, issue.repository, issue.number);
+
+    Ok(Some((repo.to_string(), created.number)))
+}
+
 fn can_ffcp_close(issue: &Issue) -> bool {
-    SETUP.should_ffcp_auto_close(&issue.repository)
+    SETUP.get().should_ffcp_auto_close(&issue.repository)
 }
 
 fn can_ffcp_postpone(issue: &Issue) -> bool {
-    SETUP.should_ffcp_auto_postpone(&issue.repository)
+    SETUP.get().should_ffcp_auto_postpone(&issue.repository)
+}
+
+fn can_ffcp_merge(issue: &Issue) -> bool {
+    issue.is_pull_request && SETUP.get().should_ffcp_auto_merge(&issue.repository)
 }
 
-fn execute_ffcp_actions(issue: &Issue, disposition: FcpDisposition) {
+/// Merge the pull request of a finished FCP, if it's still open, mergeable and its commit
+/// statuses and check runs passed.
+/// Returns whether it's merged, which it isn't yet if it isn't ready to be merged, e.g.
+/// because GitHub is still working out whether it can be merged or its checks are still
+/// running. The error explains why it can't be merged at all.
+fn merge_pull_request(issue: &Issue) -> DashResult<bool> {
+    let pr = ok_or!(GH.pull_request_status(&issue.repository, issue.number), why => {
+        warn!("Unable to retrieve {}#{}: {:?}", issue.repository, issue.number, why);
+        return Ok(false);
+    });
+
+    if pr.merged {
+        return Ok(true);
+    }
+    if pr.state != "open" {
+        throw!(DashError::Rejected("the pull request was closed".to_string()));
+    }
+    match pr.mergeable {
+        Some(true) => (),
+        Some(false) => throw!(DashError::Rejected("the pull request has conflicts".to_string())),
+        // GitHub computes this in the background, we'll ask again on the next evaluation
+        None => return Ok(false),
+    }
+
+    let status = ok_or!(GH.combined_status(&issue.repository, &pr.head.sha), why => {
+        warn!("Unable to retrieve the CI status of {}: {:?}", pr.head.sha, why);
+        return Ok(false);
+    });
+    match &*status.state {
+        _ if status.total_count == 0 => (),
+        "success" => (),
+        "pending" => return Ok(false),
+        failed => throw!(DashError::Rejected(format!("its checks haven't passed (they're {})",
+                                                     failed))),
+    }
+
+    // e.g. GitHub Actions, which only report check runs
+    let check_runs = ok_or!(GH.check_runs(&issue.repository, &pr.head.sha), why => {
+        warn!("Unable to retrieve the check runs of {}: {:?}", pr.head.sha, why);
+        return Ok(false);
+    });
+    if check_runs.check_runs.len() < check_runs.total_count as usize {
+        throw!(DashError::Rejected("it has more checks than I can look at".to_string()));
+    }
+    for run in &check_runs.check_runs {
+        match run.conclusion.as_ref().map(|conclusion| conclusion.as_str()) {
+            None => return Ok(false),
+            Some("success") | Some("neutral") | Some("skipped") => (),
+            Some(failed) => {
+                throw!(DashError::Rejected(format!("its check `{}` hasn't passed (it's {})",
+                                                   run.name, failed)))
+            }
+        }
+    }
+
+    GH.merge_pull_request(&issue.repository,
+                          issue.number,
+                          &pr.head.sha,
+                          SETUP.get().merge_method(&issue.repository))?;
+    Ok(true)
+}
+
+/// What became of carrying out the disposition of a finished FCP.
+#[derive(Debug, Eq, PartialEq)]
+enum FfcpOutcome {
+    Done,
+    /// The repo doesn't let us carry out this disposition.
+    NotAllowed,
+    /// The pull request isn't ready to be merged yet, so merging it is retried later.
+    MergePending,
+    /// The pull request can't be merged, for this reason. It isn't retried.
+    MergeFailed(String),
+}
+
+/// Carry out the disposition of a finished FCP where the repo allows it.
+fn execute_ffcp_actions(issue: &Issue, disposition: FcpDisposition) -> FfcpOutcome {
     match disposition {
-        FcpDisposition::Merge => {
-            // TODO: This one will require a lot of work to
-            // auto-merge RFCs and create the tracking issue.
+        FcpDisposition::Merge if can_ffcp_merge(issue) => {
+            match merge_pull_request(issue) {
+                Ok(true) => {
+                    issue.remove_label(Label::DispositionMerge);
+                    FfcpOutcome::Done
+                }
+                Ok(false) => FfcpOutcome::MergePending,
+                Err(why) => {
+                    error!("Unable to merge {}#{}: {:?}", issue.repository, issue.number, why);
+
+                    FfcpOutcome::MergeFailed(match why {
+                        DashError::Rejected(reason) => reason,
+                        _ => "GitHub returned an error".to_string(),
+                    })
+                }
+            }
         },
         FcpDisposition::Close if can_ffcp_close(issue) => {
             let _ = issue.add_label(Label::Closed);
             issue.remove_label(Label::DispositionClose);
             issue.close();
+            FfcpOutcome::Done
         },
         FcpDisposition::Postpone if can_ffcp_postpone(issue) => {
             let _ = issue.add_label(Label::Postponed);
             issue.remove_label(Label::DispositionPostpone);
             issue.close();
+            FfcpOutcome::Done
         },
-        _ => {},
+        _ => FfcpOutcome::NotAllowed,
     }
 }
 
@@ -412,6 +1246,7 @@ fn list_review_requests(proposal_id: i32) -> DashResult<Vec<(GitHubUser, FcpRevi
 
     let reviews = fcp_review_request::table
         .filter(fcp_review_request::fk_proposal.eq(proposal_id))
+        .filter(fcp_review_request::active.eq(true))
         .load::<FcpReviewRequest>(conn)?;
 
     let mut w_reviewers = Vec::with_capacity(reviews.len());
@@ -452,75 +1287,377 @@ fn list_concerns_with_authors(proposal_id: i32) -> DashResult<Vec<(GitHubUser, F
     Ok(w_authors)
 }
 
-fn resolve_applicable_feedback_requests(author: &GitHubUser,
-                                        issue: &Issue,
-                                        comment: &IssueComment)
-                                        -> DashResult<()> {
+/// List the feedback requested on an issue, ordered by the requested user's login.
+fn list_feedback_requests(issue_id: i32) -> DashResult<Vec<(GitHubUser, FeedbackRequest)>> {
+    use domain::schema::{githubuser, rfc_feedback_request};
+
+    let conn = &*DB_POOL.get()?;
+
+    let requests = rfc_feedback_request::table
+        .filter(rfc_feedback_request::fk_issue.eq(issue_id))
+        .load::<FeedbackRequest>(conn)?;
+
+    let mut w_requested = Vec::with_capacity(requests.len());
+
+    for request in requests {
+        let requested = githubuser::table
+            .filter(githubuser::id.eq(request.fk_requested))
+            .first::<GitHubUser>(conn)?;
+
+        w_requested.push((requested, request));
+    }
+
+    w_requested.sort_by(|a, b| a.0.login.cmp(&b.0.login));
+
+    Ok(w_requested)
+}
+
+fn resolve_applicable_feedback_requests(author: &GitHubUser,
+                                        issue: &Issue,
+                                        comment: &IssueComment)
+                                        -> DashResult<()> {
+
+    use domain::schema::rfc_feedback_request::dsl::*;
+    let conn = &*DB_POOL.get()?;
+
+    // check for an open feedback request, close since no longer applicable
+    let existing_request = rfc_feedback_request
+        .filter(fk_requested.eq(author.id))
+        .filter(fk_issue.eq(issue.id))
+        .first::<FeedbackRequest>(conn)
+        .optional()?;
+
+    if let Some(mut request) = existing_request {
+        request.fk_feedback_comment = Some(comment.id);
+        diesel::update(rfc_feedback_request.find(request.id))
+            .set(&request)
+            .execute(conn)?;
+    }
+
+    Ok(())
+}
+
+/// Check if an issue comment is written by a member of one of the subteams labelled on the issue.
+fn subteam_members(issue: &Issue) -> DashResult<Vec<GitHubUser>> {
+    let mut users = tagged_teams(issue)?
+        .into_iter()
+        .flat_map(|team| team.members)
+        .collect::<Vec<_>>();
+
+    // people can be on several teams
+    users.sort_by(|a, b| a.login.cmp(&b.login));
+    users.dedup();
+
+    Ok(users)
+}
+
+/// A team tagged on an issue, with its members resolved into users.
+struct TaggedTeam {
+    /// The team's name, or its label if it doesn't have one.
+    name: String,
+    /// The GitHub team to mention, as `org/team`.
+    ping: Option<String>,
+    members: Vec<GitHubUser>,
+    leads: Vec<String>,
+    required_reviewers: Vec<String>,
+    min_reviewers: Option<usize>,
+    /// The quorum the team has to reach when it has to reach one on its own.
+    quorum: QuorumPolicy,
+}
+
+impl TaggedTeam {
+    /// How many of the team's members have reviewed, and how many reviews are outstanding?
+    /// Abstaining members count for neither.
+    fn review_counts(&self, reviews: &[(GitHubUser, FcpReviewRequest)]) -> (usize, usize) {
+        let team_reviews = reviews.iter()
+            .filter(|&&(ref member, _)| self.members.contains(member))
+            .collect::<Vec<_>>();
+
+        let complete = team_reviews.iter().filter(|&&&(_, ref r)| r.reviewed).count();
+        let outstanding = team_reviews.iter()
+            .filter(|&&&(_, ref r)| !r.reviewed && !r.abstained)
+            .count();
+
+        (complete, outstanding)
+    }
+
+    /// Has the team reached its own quorum? A team with nobody left to review, because all of
+    /// its members abstained or it has none (e.g. they haven't been fetched from GitHub yet),
+    /// can never reach one, so it doesn't hold the proposal up.
+    fn quorum_met(&self, reviews: &[(GitHubUser, FcpReviewRequest)]) -> bool {
+        let (complete, outstanding) = self.review_counts(reviews);
+        (complete == 0 && outstanding == 0) || self.quorum.is_met(complete, outstanding)
+    }
+
+    /// Have the team's leads, required reviewers and enough of its members reviewed?
+    ///
+    /// Abstaining releases a member from these rules, so that nobody can hold a proposal up by
+    /// abstaining: an abstaining required reviewer isn't required anymore, the leads only have
+    /// to approve while one of them hasn't abstained, and `min_reviewers` is lowered to the
+    /// number of members who haven't abstained. Leads and required reviewers who aren't among
+    /// the team's members (e.g. because they were renamed on GitHub) are released the same way.
+    fn rules_met(&self, reviews: &[(GitHubUser, FcpReviewRequest)]) -> bool {
+        let team_reviews = reviews.iter()
+            .filter(|&&(ref member, _)| self.members.contains(member))
+            .collect::<Vec<_>>();
+
+        let reviewed = |login: &str| {
+            team_reviews.iter().any(|&&(ref member, ref review)| {
+                member.login == login && review.reviewed
+            })
+        };
+        let released = |login: &str| {
+            !team_reviews.iter().any(|&&(ref member, ref review)| {
+                member.login == login && !review.abstained
+            })
+        };
+
+        let leads = self.leads
+            .iter()
+            .map(|lead| lead.as_str())
+            .filter(|lead| !released(lead))
+            .collect::<Vec<_>>();
+        let leads_met = leads.is_empty() || leads.iter().any(|lead| reviewed(lead));
+        let required_met = self.required_reviewers
+            .iter()
+            .map(|required| required.as_str())
+            .all(|required| released(required) || reviewed(required));
+
+        let num_reviewed = team_reviews.iter().filter(|&&&(_, ref r)| r.reviewed).count();
+        let num_available = team_reviews.iter().filter(|&&&(_, ref r)| !r.abstained).count();
+        let min_met = self.min_reviewers
+            .map(|min| num_reviewed >= cmp::min(min, num_available))
+            .unwrap_or(true);
+
+        leads_met && required_met && min_met
+    }
+
+    /// Describes the team's rules for the tracking comment, e.g. "a lead of the Core team
+    /// approves".
+    fn rule_descriptions(&self) -> Vec<String> {
+        let mut rules = Vec::new();
+
+        if !self.leads.is_empty() {
+            rules.push(format!("a lead of {} approves", self.name));
+        }
+        match self.required_reviewers.len() {
+            0 => (),
+            1 => rules.push(format!("@{} approves", self.required_reviewers[0])),
+            _ => {
+                let required = self.required_reviewers
+                    .iter()
+                    .map(|login| format!("@{}", login))
+                    .collect::<Vec<_>>();
+                rules.push(format!("{} approve", required.join(", ")))
+            }
+        }
+        if let Some(min) = self.min_reviewers {
+            rules.push(format!("at least {} members of {} approve", min, self.name));
+        }
+
+        rules
+    }
+
+    /// What a member is on the team besides a reviewer, e.g. "(lead)", for the checklist.
+    fn roles(&self, login: &str) -> String {
+        let mut roles = Vec::new();
+        if self.leads.iter().any(|lead| lead == login) {
+            roles.push("lead");
+        }
+        if self.required_reviewers.iter().any(|required| required == login) {
+            roles.push("required");
+        }
+
+        if roles.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", roles.join(", "))
+        }
+    }
+}
+
+/// Retrieve the teams tagged on an issue.
+fn tagged_teams(issue: &Issue) -> DashResult<Vec<TaggedTeam>> {
+    use diesel::pg::expression::dsl::any;
+    use domain::schema::githubuser;
+
+    let conn = &*DB_POOL.get()?;
+
+    let setup = SETUP.get();
+    let mut teams = Vec::new();
+    for (label, config) in setup.teams().filter(|&(label, _)| issue.labels.contains(&label.0)) {
+        let quorum = setup.team_quorum_policy(&issue.repository, config);
+
+        let member_logins = config.member_logins().collect::<Vec<_>>();
+
+        // plus the members of its GitHub team, as last fetched by the scraper
+        let fetched_member_ids = match config.github_team() {
+            Some(github_team) => {
+                use domain::schema::github_team_member::dsl::*;
+                github_team_member
+                    .select(fk_member)
+                    .filter(team.eq(github_team))
+                    .load::<i32>(conn)?
+            }
+            None => Vec::new(),
+        };
+
+        // resolve each member into an actual user
+        let members = githubuser::table
+            .filter(githubuser::login.eq(any(&member_logins))
+                        .or(githubuser::id.eq(any(&fetched_member_ids))))
+            .order(githubuser::login)
+            .load::<GitHubUser>(conn)?;
+
+        teams.push(TaggedTeam {
+            name: config.name().unwrap_or(&label.0).to_string(),
+            ping: config.ping().map(String::from),
+            members: members,
+            leads: config.leads().to_vec(),
+            required_reviewers: config.required_reviewers().to_vec(),
+            min_reviewers: config.min_reviewers(),
+            quorum: quorum,
+        });
+    }
+
+    Ok(teams)
+}
+
+/// Cancel a proposal, keeping it with its concerns and reviews as history.
+///
+/// `author` and `comment` are `None` when we cancel a proposal on our own, e.g. because its
+/// issue was closed.
+fn cancel_fcp(author: Option<&GitHubUser>,
+              comment: Option<&IssueComment>,
+              issue: &Issue,
+              existing: &FcpProposal)
+              -> DashResult<()> {
+    let mut cancelled = existing.clone();
+    cancelled.fk_cancelled_by = author.map(|a| a.id);
+    cancelled.fk_cancelling_comment = comment.map(|c| c.id);
+    transition(&mut cancelled, FcpState::Cancelled)?;
+
+    // leave github comment stating that FCP proposal cancelled
+    if let Some(author) = author {
+        let comment = MscbotComment::new(issue, CommentType::FcpProposalCancelled(author));
+        let _ = comment.post(None);
+    }
+    &[Label::FCP,
+      Label::PFCP,
+      Label::DispositionMerge,
+      Label::DispositionClose,
+      Label::DispositionPostpone,
+    ].iter().for_each(|&lab| issue.remove_label(lab));
+
+    Ok(())
+}
+
+/// Change the disposition of an existing proposal, swapping its labels and resetting its
+/// reviews if the repo asks for that.
+fn change_disposition(author: &GitHubUser,
+                      issue: &Issue,
+                      mut proposal: FcpProposal,
+                      new_disposition: FcpDisposition)
+                      -> DashResult<()> {
+    use domain::schema::fcp_review_request;
 
-    use domain::schema::rfc_feedback_request::dsl::*;
     let conn = &*DB_POOL.get()?;
 
-    // check for an open feedback request, close since no longer applicable
-    let existing_request = rfc_feedback_request
-        .filter(fk_requested.eq(author.id))
-        .filter(fk_issue.eq(issue.id))
-        .first::<FeedbackRequest>(conn)
-        .optional()?;
+    let reset = SETUP.get().disposition_change_reviews(&issue.repository) == ReviewReset::Reset;
 
-    if let Some(mut request) = existing_request {
-        request.fk_feedback_comment = Some(comment.id);
-        diesel::update(rfc_feedback_request.find(request.id))
-            .set(&request)
-            .execute(conn)?;
+    let current = proposal.fcp_state()?;
+    if !current.is_live() {
+        throw!(DashError::Rejected(format!("the FCP is over (this proposal is {}), so its \
+                                            disposition can't be changed anymore. Please \
+                                            propose a new FCP instead.",
+                                           current.description())));
     }
 
-    Ok(())
-}
+    // the teams have to sign off on the new disposition before FCP can (re)start
+    let next = if reset { FcpState::Proposed } else { current };
+    current.ensure_transition_to(next)?;
 
-/// Check if an issue comment is written by a member of one of the subteams labelled on the issue.
-fn subteam_members(issue: &Issue) -> DashResult<Vec<GitHubUser>> {
-    use diesel::pg::expression::dsl::any;
-    use domain::schema::githubuser;
+    let old_disposition = FcpDisposition::from_str(&proposal.disposition)?;
+    if old_disposition == new_disposition {
+        return Ok(());
+    }
 
-    let conn = &*DB_POOL.get()?;
+    proposal.disposition = new_disposition.repr().to_string();
+
+    let left_fcp = current == FcpState::InFcp && next == FcpState::Proposed;
+
+    if reset {
+        // just like a new proposal, only the author is assumed to have reviewed it -- this goes
+        // for inactive requests too, they'd bring an outdated review back otherwise
+        let review_requests = fcp_review_request::table
+            .filter(fcp_review_request::fk_proposal.eq(proposal.id))
+            .load::<FcpReviewRequest>(conn)?;
+        for mut review_request in review_requests {
+            let reviewed = review_request.fk_reviewer == author.id && !review_request.abstained;
+            if review_request.reviewed != reviewed {
+                review_request.reviewed = reviewed;
+                diesel::update(fcp_review_request::table.find(review_request.id))
+                    .set(&review_request)
+                    .execute(conn)?;
+            }
+        }
+    }
 
-    // retrieve all of the teams tagged on this issue
-    // cannot WAIT for by-ref/by-val inference
-    let member_logins = SETUP.teams()
-        .filter(|&(ref label, _)| issue.labels.contains(&label.0))
-        .flat_map(|(_, team)| team.member_logins())
-        .collect::<BTreeSet<_>>()
-        .into_iter() // diesel won't work with btreeset, and dedup has weird lifetime errors
-        .collect::<Vec<_>>();
+    diesel::update(fcp_proposal::table.find(proposal.id))
+        .set(&proposal)
+        .execute(conn)?;
+    transition(&mut proposal, next)?;
+
+    issue.remove_label(old_disposition.label());
+    let _ = issue.add_label(new_disposition.label());
+    if left_fcp {
+        let _ = issue.add_label(Label::PFCP);
+        issue.remove_label(Label::FCP);
+    }
 
-    // resolve each member into an actual user
-    let users = githubuser::table
-        .filter(githubuser::login.eq(any(&member_logins)))
-        .order(githubuser::login)
-        .load::<GitHubUser>(conn)?;
+    // rewrite the tracking comment right away, otherwise the next evaluation would read the
+    // old checkboxes back in and undo the reset
+    ok_or!(update_status_comment(issue, &proposal), why =>
+        error!("Unable to update status comment for proposal {}: {:?}", proposal.id, why));
+
+    let comment = MscbotComment::new(issue, CommentType::FcpDispositionChanged {
+        author: author,
+        old_disposition: old_disposition,
+        new_disposition: new_disposition,
+        reviews_reset: reset,
+        left_fcp: left_fcp,
+    });
+    let _ = comment.post(None);
 
-    Ok(users)
+    Ok(())
 }
 
-fn cancel_fcp(author: &GitHubUser, issue: &Issue, existing: &FcpProposal) -> DashResult<()> {
-    use domain::schema::fcp_proposal::dsl::*;
-
+/// Re-render a proposal's tracking comment from the database and post it.
+fn update_status_comment(issue: &Issue, proposal: &FcpProposal) -> DashResult<()> {
     let conn = &*DB_POOL.get()?;
 
-    // if exists delete FCP with associated concerns, reviews, feedback requests
-    // db schema has ON DELETE CASCADE
-    diesel::delete(fcp_proposal.filter(id.eq(existing.id)))
+    let initiator = githubuser::table
+        .find(proposal.fk_initiator)
+        .first::<GitHubUser>(conn)?;
+    let reviews = list_review_requests(proposal.id)?;
+    let concerns = list_concerns_with_authors(proposal.id)?;
+    let feedback = list_feedback_requests(issue.id)?;
+    let teams = tagged_teams(issue)?;
+
+    let status_comment = MscbotComment::new(issue, CommentType::FcpProposed(
+        &initiator,
+        FcpDisposition::from_str(&proposal.disposition)?,
+        &teams,
+        &reviews,
+        &concerns,
+        &feedback));
+
+    // keep our copy in sync even if posting is disabled, it's what checkboxes are read from
+    diesel::update(issuecomment::table.find(proposal.fk_bot_tracking_comment))
+        .set(issuecomment::body.eq(&status_comment.body))
         .execute(conn)?;
 
-    // leave github comment stating that FCP proposal cancelled
-    let comment = MscbotComment::new(issue, CommentType::FcpProposalCancelled(author));
-    let _ = comment.post(None);
-    &[Label::FCP,
-      Label::PFCP,
-      Label::DispositionMerge,
-      Label::DispositionClose,
-      Label::DispositionPostpone,
-    ].iter().for_each(|&lab| issue.remove_label(lab));
+    status_comment.post(Some(proposal.fk_bot_tracking_comment))?;
 
     Ok(())
 }
@@ -528,8 +1665,11 @@ fn cancel_fcp(author: &GitHubUser, issue: &Issue, existing: &FcpProposal) -> Das
 #[derive(Debug, Eq, PartialEq)]
 pub enum MscbotCommand<'a> {
     FcpPropose(FcpDisposition),
+    FcpChangeDisposition(FcpDisposition),
     FcpCancel,
     Reviewed,
+    Abstain,
+    Unabstain,
     NewConcern(&'a str),
     ResolveConcern(&'a str),
     FeedbackRequest(&'a str),
@@ -573,6 +1713,72 @@ impl FcpDisposition {
     }
 }
 
+/// Tell the author of a comment why we didn't act on it.
+fn reject_command(issue: &Issue, author: &GitHubUser, reason: &str) {
+    let comment = MscbotComment::new(issue, CommentType::CommandRejected {
+        author: author,
+        reason: reason,
+    });
+
+    ok_or!(comment.post(None), why =>
+        warn!("Unable to explain rejected command to {} on {}#{}: {:?}",
+              author.login, issue.repository, issue.number, why));
+}
+
+/// The words which can follow `@mscbot`, `@mscbot fcp` or `@mscbot pr`, used to suggest a fix
+/// for typos.
+const SUBCOMMANDS: &'static [&'static str] = &[
+    "merge", "close", "postpone", "cancel", "reviewed", "abstain", "unabstain",
+    "disposition", "concern", "resolve",
+];
+
+/// Find the known word closest to a misspelled one, if any is close enough.
+fn suggest<'a>(word: &str, candidates: &[&'a str]) -> Option<&'a str> {
+    candidates.iter()
+              .map(|&candidate| (edit_distance(word, candidate), candidate))
+              .filter(|&(distance, _)| distance <= 2)
+              .min()
+              .map(|(_, candidate)| candidate)
+}
+
+/// The Levenshtein distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..b.len() + 1).collect::<Vec<_>>();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = previous[j] + if ca == cb { 0 } else { 1 };
+            let insertion = current[j] + 1;
+            let deletion = previous[j + 1] + 1;
+            current.push(substitution.min(insertion).min(deletion));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+/// Describe an unknown command word, with a suggestion if it looks like a typo.
+fn unknown_word(kind: &str, word: &str, candidates: &[&str]) -> DashError {
+    DashError::Rejected(match suggest(word, candidates) {
+        Some(suggestion) => format!("unknown {} `{}`, did you mean `{}`?",
+                                    kind, word, suggestion),
+        None => format!("unknown {} `{}`", kind, word),
+    })
+}
+
+/// Parses a disposition, accepting the same inflections as a proposal does.
+fn parse_disposition(word: &str) -> Option<FcpDisposition> {
+    match word {
+        "merge" | "merged" | "merging" | "merges" => Some(FcpDisposition::Merge),
+        "close" | "closed" | "closing" | "closes" => Some(FcpDisposition::Close),
+        "postpone" | "postponed" | "postponing" | "postpones" => Some(FcpDisposition::Postpone),
+        _ => None,
+    }
+}
+
 /// Parses the text of a subcommand.
 fn parse_command_text<'a>(command: &'a str, subcommand: &'a str) -> &'a str {
     let name_start = command.find(subcommand).unwrap() + subcommand.len();
@@ -590,6 +1796,9 @@ fn parse_command_text<'a>(command: &'a str, subcommand: &'a str) -> &'a str {
 /// postpone ::= "postpone" | "postponed" | "postponing" | "postpones" ;
 /// cancel ::= "cancel | "canceled" | "canceling" | "cancels" ;
 /// review ::= "reviewed" | "review" | "reviewing" | "reviews" ;
+/// abstain ::= "abstain" | "abstained" | "abstaining" | "abstains" ;
+/// unabstain ::= "unabstain" | "unabstained" | "unabstaining" | "unabstains" ;
+/// disposition ::= merge | close | postpone ;
 /// concern ::= "concern" | "concerned" | "concerning" | "concerns" ;
 /// resolve ::= "resolve" | "resolved" | "resolving" | "resolves" ;
 ///
@@ -597,6 +1806,8 @@ fn parse_command_text<'a>(command: &'a str, subcommand: &'a str) -> &'a str {
 /// ws_separated ::= ... ;
 ///
 /// subcommand ::= merge | close | postpone | cancel | review
+///              | abstain | unabstain
+///              | "disposition" disposition
 ///              | concern line_remainder
 ///              | resolve line_remainder
 ///              ;
@@ -613,18 +1824,25 @@ fn parse_fcp_subcommand<'a>(
     subcommand: &'a str,
     fcp_context: bool
 ) -> DashResult<MscbotCommand<'a>> {
-    Ok(match subcommand {
-        // Parse a FCP merge command:
-        "merge" | "merged" | "merging" | "merges" =>
-            MscbotCommand::FcpPropose(FcpDisposition::Merge),
-
-        // Parse a FCP close command:
-        "close" | "closed" | "closing" | "closes" =>
-            MscbotCommand::FcpPropose(FcpDisposition::Close),
+    // Parse a FCP merge, close or postpone command:
+    if let Some(disp) = parse_disposition(subcommand) {
+        return Ok(MscbotCommand::FcpPropose(disp));
+    }
 
-        // Parse a FCP postpone command:
-        "postpone" | "postponed" | "postponing" | "postpones" =>
-            MscbotCommand::FcpPropose(FcpDisposition::Postpone),
+    Ok(match subcommand {
+        // Parse a FCP disposition change command:
+        "disposition" => {
+            debug!("Parsed command as FcpChangeDisposition");
+            let what = parse_command_text(command, subcommand);
+            let word = what.split_whitespace().next().ok_or_else(|| {
+                DashError::Rejected("`disposition` needs the new disposition, \
+                                     e.g. `disposition close`".to_string())
+            })?;
+            let disp = parse_disposition(word).ok_or_else(|| {
+                unknown_word("disposition", word, &["merge", "close", "postpone"])
+            })?;
+            MscbotCommand::FcpChangeDisposition(disp)
+        },
 
         // Parse a FCP cancel command:
         "cancel" | "canceled" | "canceling" | "cancels" =>
@@ -634,10 +1852,21 @@ fn parse_fcp_subcommand<'a>(
         "reviewed" | "review" | "reviewing" | "reviews" =>
             MscbotCommand::Reviewed,
 
+        // Parse a FCP abstain command:
+        "abstain" | "abstained" | "abstaining" | "abstains" =>
+            MscbotCommand::Abstain,
+
+        // Parse a FCP unabstain command:
+        "unabstain" | "unabstained" | "unabstaining" | "unabstains" =>
+            MscbotCommand::Unabstain,
+
         // Parse a FCP concern command:
         "concern" | "concerned" | "concerning" | "concerns" => {
             debug!("Parsed command as NewConcern");
             let what = parse_command_text(command, subcommand);
+            if what.is_empty() {
+                throw!(DashError::Rejected("a concern needs a name".to_string()));
+            }
             MscbotCommand::NewConcern(what)
         },
 
@@ -645,16 +1874,21 @@ fn parse_fcp_subcommand<'a>(
         "resolve" | "resolved" | "resolving" | "resolves" => {
             debug!("Parsed command as ResolveConcern");
             let what = parse_command_text(command, subcommand);
+            if what.is_empty() {
+                throw!(DashError::Rejected("tell me which concern to resolve".to_string()));
+            }
             MscbotCommand::ResolveConcern(what)
         },
 
         _ => {
-            throw!(DashError::Misc(if fcp_context {
+            if fcp_context {
                 error!("unrecognized subcommand for fcp: {}", subcommand);
-                Some(format!("found bad subcommand: {}", subcommand))
+                throw!(unknown_word("subcommand", subcommand, SUBCOMMANDS))
             } else {
-                None
-            }))
+                let mut commands = vec!["fcp", "pr", "f?"];
+                commands.extend(SUBCOMMANDS);
+                throw!(unknown_word("command", subcommand, &commands))
+            }
         }
     })
 }
@@ -669,12 +1903,14 @@ impl<'a> MscbotCommand<'a> {
 
         let conn = &*DB_POOL.get()?;
 
-        // check for existing FCP
+        // check for existing FCP -- the latest one, an issue can have several finished ones
         let existing_proposal = {
             use domain::schema::fcp_proposal::dsl::*;
 
             fcp_proposal
                 .filter(fk_issue.eq(issue.id))
+                .filter(state.ne(FcpState::Cancelled.repr()))
+                .order(id.desc())
                 .first::<FcpProposal>(conn)
                 .optional()?
         };
@@ -685,13 +1921,24 @@ impl<'a> MscbotCommand<'a> {
                 use domain::schema::fcp_proposal::dsl::*;
                 use domain::schema::{fcp_review_request, issuecomment};
 
-                if existing_proposal.is_none() {
+                // once an FCP is over, the issue can go through another one
+                let live_proposal = match existing_proposal {
+                    Some(ref existing) => existing.fcp_state()?.is_live(),
+                    None => false,
+                };
+
+                if !live_proposal {
                     // if not exists, create new FCP proposal
                     info!("proposal is a new FCP, creating...");
 
+                    if existing_proposal.is_some() {
+                        issue.remove_label(Label::FFCP);
+                    }
+
                     // leave github comment stating that FCP is proposed, ping reviewers
                     let gh_comment =
-                        MscbotComment::new(issue, CommentType::FcpProposed(author, disp, &[], &[]));
+                        MscbotComment::new(issue,
+                            CommentType::FcpProposed(author, disp, &[], &[], &[], &[]));
 
                     let gh_comment = gh_comment.post(None)?;
                     info!("Posted base comment to github, no reviewers listed yet");
@@ -715,12 +1962,16 @@ impl<'a> MscbotCommand<'a> {
                         disposition: disp.repr(),
                         fk_bot_tracking_comment: gh_comment.id,
                         fcp_start: None,
-                        fcp_closed: false,
+                        state: FcpState::Proposed.repr(),
                     };
 
                     let proposal = diesel::insert(&proposal)
                         .into(fcp_proposal)
                         .get_result::<FcpProposal>(conn)?;
+                    record_transition(proposal.id,
+                                      None,
+                                      FcpState::Proposed,
+                                      Utc::now().naive_utc())?;
 
                     debug!("proposal inserted into the database");
 
@@ -734,6 +1985,8 @@ impl<'a> MscbotCommand<'a> {
                                      fk_proposal: proposal.id,
                                      fk_reviewer: member.id,
                                      reviewed: member.id == author.id,
+                                     abstained: false,
+                                     active: true,
                                  }
                              })
                         .collect::<Vec<_>>();
@@ -745,6 +1998,8 @@ impl<'a> MscbotCommand<'a> {
                     // they're in the database, but now we need them paired with githubuser
 
                     let review_requests = list_review_requests(proposal.id)?;
+                    let feedback_requests = list_feedback_requests(issue.id)?;
+                    let teams = tagged_teams(issue)?;
 
                     debug!("review requests inserted into the database");
 
@@ -752,17 +2007,27 @@ impl<'a> MscbotCommand<'a> {
 
                     let new_gh_comment =
                         MscbotComment::new(issue,
-                            CommentType::FcpProposed(
-                                author, disp, &review_requests, &[]));
+                            CommentType::FcpProposed(author,
+                                                     disp,
+                                                     &teams,
+                                                     &review_requests,
+                                                     &[],
+                                                     &feedback_requests));
 
                     new_gh_comment.post(Some(gh_comment.id))?;
 
                     debug!("github comment updated with reviewers");
                 }
             }
+            MscbotCommand::FcpChangeDisposition(disp) => {
+                debug!("processing disposition change: {:?}", disp);
+                if let Some(existing) = existing_proposal {
+                    change_disposition(author, issue, existing, disp)?;
+                }
+            }
             MscbotCommand::FcpCancel => {
                 if let Some(existing) = existing_proposal {
-                    cancel_fcp(author, issue, &existing)?;
+                    cancel_fcp(Some(author), Some(comment), issue, &existing)?;
                 }
             }
             MscbotCommand::Reviewed => {
@@ -771,6 +2036,7 @@ impl<'a> MscbotCommand<'a> {
                 use domain::schema::fcp_review_request::dsl::*;
 
                 if let Some(proposal) = existing_proposal {
+                    proposal.fcp_state()?.ensure_live("its reviews")?;
 
                     let review_request = fcp_review_request
                         .filter(fk_proposal.eq(proposal.id))
@@ -779,9 +2045,7 @@ impl<'a> MscbotCommand<'a> {
                         .optional()?;
 
                     if let Some(mut review_request) = review_request {
-                        // store an FK to the comment marking for review (not null fk here means
-                        // reviewed)
-                        review_request.reviewed = true;
+                        mark_reviewed(&mut review_request);
 
                         diesel::update(fcp_review_request.find(review_request.id))
                             .set(&review_request)
@@ -790,12 +2054,44 @@ impl<'a> MscbotCommand<'a> {
 
                 }
             }
+            MscbotCommand::Abstain | MscbotCommand::Unabstain => {
+                // (un)mark the comment author as abstaining from this proposal
+                let abstain = self == MscbotCommand::Abstain;
+
+                use domain::schema::fcp_review_request::dsl::*;
+
+                if let Some(proposal) = existing_proposal {
+                    proposal.fcp_state()?.ensure_live("its abstentions")?;
+
+                    let review_request = fcp_review_request
+                        .filter(fk_proposal.eq(proposal.id))
+                        .filter(fk_reviewer.eq(author.id))
+                        .first::<FcpReviewRequest>(conn)
+                        .optional()?;
+
+                    if let Some(mut review_request) = review_request {
+                        // an abstention replaces any earlier review
+                        review_request.abstained = abstain;
+                        if abstain {
+                            review_request.reviewed = false;
+                        }
+
+                        diesel::update(fcp_review_request.find(review_request.id))
+                            .set(&review_request)
+                            .execute(conn)?;
+                    }
+                }
+            }
             MscbotCommand::NewConcern(concern_name) => {
 
                 if let Some(mut proposal) = existing_proposal {
+                    // a concern sends the proposal back for review, which isn't possible
+                    // once the FCP has finished
+                    let current = proposal.fcp_state()?;
+                    current.ensure_transition_to(FcpState::Proposed)?;
+
                     // check for existing concern
                     use domain::schema::fcp_concern::dsl::*;
-                    use domain::schema::fcp_proposal::dsl::*;
 
                     let existing_concern = fcp_concern
                         .filter(fk_proposal.eq(proposal.id))
@@ -819,13 +2115,9 @@ impl<'a> MscbotCommand<'a> {
                             .execute(conn)?;
             
                         // Take us out of FCP and back into PFCP if need be:
-                        if proposal.fcp_start.is_some() {
+                        if current == FcpState::InFcp {
                             // Update DB: FCP is not started anymore.
-                            proposal.fcp_start = None;
-                            let update =
-                                diesel::update(fcp_proposal.find(proposal.id))
-                                      .set(&proposal)
-                                      .execute(conn);
+                            let update = transition(&mut proposal, FcpState::Proposed);
                             ok_or!(update, why => {
                                 error!("Unable to mark FCP {} as unstarted: {:?}", proposal.id, why);
                                 return Ok(());
@@ -843,6 +2135,8 @@ impl<'a> MscbotCommand<'a> {
                 debug!("Command is to resolve a concern ({}).", concern_name);
 
                 if let Some(proposal) = existing_proposal {
+                    proposal.fcp_state()?.ensure_live("its concerns")?;
+
                     // check for existing concern
                     use domain::schema::fcp_concern::dsl::*;
 
@@ -872,12 +2166,20 @@ impl<'a> MscbotCommand<'a> {
                 use domain::schema::githubuser;
                 use domain::schema::rfc_feedback_request::dsl::*;
 
-                // we'll just assume that this user exists...it's very unlikely that someone
-                // will request feedback from a user who's *never* commented or committed
-                // on/to a rust-lang* repo
+                // we only know users who have been active in one of our repos
                 let requested_user = githubuser::table
                     .filter(githubuser::login.eq(username))
-                    .first::<GitHubUser>(conn)?;
+                    .first::<GitHubUser>(conn)
+                    .optional()?;
+
+                let requested_user = match requested_user {
+                    Some(user) => user,
+                    None => {
+                        let reason = format!("I don't know @{}, so I can't ask them for feedback.",
+                                             username);
+                        throw!(DashError::Rejected(reason))
+                    }
+                };
 
                 // check for existing feedback request
                 let existing_request = rfc_feedback_request
@@ -906,36 +2208,55 @@ impl<'a> MscbotCommand<'a> {
         Ok(())
     }
 
-    pub fn from_str_all(command: &'a str) -> impl Iterator<Item = MscbotCommand<'a>> {
+    pub fn from_str_all(command: &'a str,
+                        mentions: &'a [String])
+                        -> impl Iterator<Item = MscbotCommand<'a>> {
+        Self::parse_all(command, mentions).filter_map(|(_, parsed)| parsed.ok())
+    }
+
+    /// Parse every invocation line of a comment, keeping the line along with the outcome so
+    /// that malformed commands can be explained to their author.
+    pub fn parse_all(command: &'a str,
+                     mentions: &'a [String])
+                     -> impl Iterator<Item = (&'a str, DashResult<MscbotCommand<'a>>)> {
         // Get the tokens for each command line (starts with a bot mention)
         command.lines()
-               .filter(|&l| l.starts_with(MSC_BOT_MENTION))
-               .map(Self::from_invocation_line)
-               .filter_map(Result::ok)
+               .filter_map(move |l| {
+                   strip_mention(l, mentions).map(|invocation| {
+                       (l, Self::from_invocation(invocation))
+                   })
+               })
     }
 
-    fn from_invocation_line(command: &'a str) -> DashResult<MscbotCommand<'a>> {
-        let mut tokens = command.trim_left_matches(MSC_BOT_MENTION)
-                                .trim_left_matches(':')
+    /// Parse what follows the bot mention on a command line.
+    fn from_invocation(command: &'a str) -> DashResult<MscbotCommand<'a>> {
+        let mut tokens = command.trim_left_matches(':')
                                 .trim()
                                 .split_whitespace();
-        let invocation = tokens.next().ok_or(DashError::Misc(None))?;
+        let invocation = tokens.next().ok_or_else(|| {
+            DashError::Rejected("there's no command after the mention".to_string())
+        })?;
         match invocation {
             "fcp" | "pr" => {
-                let subcommand = tokens.next().ok_or(DashError::Misc(None))?;
+                let subcommand = tokens.next().ok_or_else(|| {
+                    DashError::Rejected(format!("`{}` needs a subcommand, e.g. `{} merge`",
+                                                invocation, invocation))
+                })?;
 
                 debug!("Parsed command as new FCP proposal");
 
                 parse_fcp_subcommand(command, subcommand, true)
             }
             "f?" => {
-                let user =
-                    tokens
-                        .next()
-                        .ok_or_else(|| DashError::Misc(Some("no user specified".to_string())))?;
+                let no_user = || {
+                    DashError::Rejected("`f?` needs a user to ask for feedback, \
+                                         e.g. `f? @username`".to_string())
+                };
 
-                if user.is_empty() {
-                    throw!(DashError::Misc(Some("no user specified".to_string())));
+                let user = tokens.next().ok_or_else(&no_user)?;
+
+                if !user.starts_with('@') || user.len() < 2 {
+                    throw!(no_user());
                 }
 
                 Ok(MscbotCommand::FeedbackRequest(&user[1..]))
@@ -946,6 +2267,23 @@ impl<'a> MscbotCommand<'a> {
 
 }
 
+/// The rest of a line which starts with one of the bot's mentions, `None` if it doesn't.
+///
+/// A mention has to be followed by something which can't be part of a username, so that e.g.
+/// `@mscbot` doesn't match `@mscbot-staging`.
+fn strip_mention<'a>(line: &'a str, mentions: &[String]) -> Option<&'a str> {
+    mentions.iter()
+            .filter(|mention| line.starts_with(mention.as_str()))
+            .map(|mention| &line[mention.len()..])
+            .find(|rest| {
+                !rest.starts_with(|c: char| c.is_alphanumeric() || c == '-' || c == '_')
+            })
+}
+
+/// Headers of the concerns section of a tracking comment, which ends the reviewer checklist.
+const NO_CONCERNS: &'static str = "No concerns currently listed.";
+const CONCERNS: &'static str = "Concerns:";
+
 struct MscbotComment<'a> {
     issue: &'a Issue,
     body: String,
@@ -955,19 +2293,40 @@ struct MscbotComment<'a> {
 enum CommentType<'a> {
     FcpProposed(&'a GitHubUser,
                 FcpDisposition,
+                &'a [TaggedTeam],
                 &'a [(GitHubUser, FcpReviewRequest)],
-                &'a [(GitHubUser, FcpConcern)]),
+                &'a [(GitHubUser, FcpConcern)],
+                &'a [(GitHubUser, FeedbackRequest)]),
     FcpProposalCancelled(&'a GitHubUser),
+    CommandRejected {
+        author: &'a GitHubUser,
+        reason: &'a str,
+    },
+    MergeFailed {
+        author: &'a GitHubUser,
+        reason: &'a str,
+    },
+    FcpDispositionChanged {
+        author: &'a GitHubUser,
+        old_disposition: FcpDisposition,
+        new_disposition: FcpDisposition,
+        reviews_reset: bool,
+        left_fcp: bool,
+    },
     FcpAllReviewedNoConcerns {
         author: &'a GitHubUser,
         status_comment_id: i32,
         added_label: bool,
+        duration: Duration,
+        fcp_end: NaiveDateTime,
     },
     FcpWeekPassed {
         author: &'a GitHubUser,
         status_comment_id: i32,
         added_label: bool,
-        disposition: FcpDisposition
+        disposition: FcpDisposition,
+        outcome: &'a FfcpOutcome,
+        follow_up: Option<(&'a str, i32)>,
     },
 }
 
@@ -983,126 +2342,309 @@ impl<'a> MscbotComment<'a> {
         }
     }
 
-    fn couldnt_add_label<'b>(msg: &mut String, author: &'b GitHubUser, label: Label) {
+    fn couldnt_add_label<'b>(msg: &mut String,
+                             issue: &Issue,
+                             author: &'b GitHubUser,
+                             label: Label) {
+        let name = issue.label_name(label).unwrap_or_else(|| label.as_str().to_string());
+
         msg.push_str("\n\n*psst @");
         msg.push_str(&author.login);
         msg.push_str(", I wasn't able to add the `");
-        msg.push_str(label.as_str());
+        msg.push_str(&name);
         msg.push_str("` label, please do so.*");
     }
 
     fn format(issue: &Issue, comment_type: &CommentType) -> String {
-
-        match *comment_type {
-            CommentType::FcpProposed(initiator, disposition, reviewers, concerns) => {
-                let mut msg = String::from("Team member @");
-                msg.push_str(&initiator.login);
-                msg.push_str(" has proposed to ");
-                msg.push_str(disposition.repr());
-                msg.push_str(" this. The next step is review by the rest of the tagged ");
-                msg.push_str("teams:\n\n");
-
-                for &(ref member, ref review_request) in reviewers {
-
-                    if review_request.reviewed {
-                        msg.push_str("* [x] @");
-                    } else {
-                        msg.push_str("* [ ] @");
-                    }
-
-                    msg.push_str(&member.login);
-                    msg.push('\n');
-                }
-
-                if concerns.is_empty() {
-                    msg.push_str("\nNo concerns currently listed.\n");
+        let repo = &issue.repository;
+
+        let rendered = match *comment_type {
+            CommentType::FcpProposed(initiator,
+                                     disposition,
+                                     teams,
+                                     reviewers,
+                                     concerns,
+                                     feedback) => {
+                let setup = SETUP.get();
+                let per_team_quorum = setup.per_team_quorum(repo) && !teams.is_empty();
+
+                let policies = if per_team_quorum {
+                    teams.iter()
+                        .map(|team| format!("{} on {}", team.quorum.description(), team.name))
+                        .collect::<Vec<_>>()
                 } else {
-                    msg.push_str("\nConcerns:\n\n");
-                }
-
-                for &(_, ref concern) in concerns {
-
-                    if let Some(resolved_comment_id) = concern.fk_resolved_comment {
-                        msg.push_str("* ~~");
-                        msg.push_str(&concern.name);
-                        msg.push_str("~~ resolved by ");
-                        Self::add_comment_url(issue, &mut msg, resolved_comment_id);
-                        msg.push_str("\n");
-
-                    } else {
-                        msg.push_str("* ");
-                        msg.push_str(&concern.name);
-                        msg.push_str(" (");
-                        Self::add_comment_url(issue, &mut msg, concern.fk_initiating_comment);
-                        msg.push_str(")\n");
-                    }
-                }
+                    setup.quorum_policies(&issue.repository, &issue.labels)
+                        .into_iter()
+                        .map(|policy| policy.description())
+                        .collect::<Vec<_>>()
+                };
+                let quorum = policies.into_iter()
+                    .chain(teams.iter().flat_map(|team| team.rule_descriptions()))
+                    .collect::<Vec<_>>()
+                    .join(" and ");
+
+                templates::render(repo, "fcp-proposed", &json!({
+                    "initiator": initiator.login,
+                    "disposition": disposition.repr(),
+                    "checklist": Self::checklist(issue,
+                                                 teams,
+                                                 per_team_quorum,
+                                                 reviewers,
+                                                 concerns,
+                                                 feedback),
+                    "quorum": quorum,
+                    "teams": teams.iter().map(|team| json!({
+                        "name": team.name,
+                        "ping": team.ping,
+                    })).collect::<Vec<_>>(),
+                }))
+            }
 
-                msg.push_str("\nOnce a majority of reviewers approve (and none object), this will enter its final ");
-                msg.push_str("comment period. If you spot a major issue that hasn't been raised ");
-                msg.push_str("at any point in this process, please speak up!\n");
+            CommentType::FcpProposalCancelled(initiator) => {
+                templates::render(repo, "fcp-cancelled", &json!({
+                    "author": initiator.login,
+                }))
+            }
 
-                msg.push_str("\nSee [this document](");
-                msg.push_str("https://github.com/anp/mscbot-rs/blob/master/README.md");
-                msg.push_str(") for info about what commands tagged team members can give me.");
+            CommentType::CommandRejected { author, reason } => {
+                templates::render(repo, "command-rejected", &json!({
+                    "author": author.login,
+                    "reason": reason,
+                }))
+            }
 
-                msg
+            CommentType::MergeFailed { author, reason } => {
+                templates::render(repo, "merge-failed", &json!({
+                    "author": author.login,
+                    "reason": reason,
+                }))
             }
 
-            CommentType::FcpProposalCancelled(initiator) => {
-                format!("@{} proposal cancelled.", initiator.login)
+            CommentType::FcpDispositionChanged {
+                author,
+                old_disposition,
+                new_disposition,
+                reviews_reset,
+                left_fcp,
+            } => {
+                templates::render(repo, "disposition-changed", &json!({
+                    "author": author.login,
+                    "old_disposition": old_disposition.repr(),
+                    "new_disposition": new_disposition.repr(),
+                    "reviews_reset": reviews_reset,
+                    "left_fcp": left_fcp,
+                }))
             }
 
             CommentType::FcpAllReviewedNoConcerns {
-                author,
                 status_comment_id,
-                added_label,
+                duration,
+                fcp_end,
+                ..
+            } => {
+                templates::render(repo, "fcp-started", &json!({
+                    "review_url": Self::comment_url(issue, status_comment_id),
+                    "duration_days": duration.num_days(),
+                    "fcp_end": fcp_end.format("%Y-%m-%d %H:%M").to_string(),
+                }))
+            }
+
+            CommentType::FcpWeekPassed {
+                status_comment_id,
+                disposition,
+                outcome,
+                follow_up,
+                ..
             } => {
-                let mut msg = String::new();
+                // only what has actually been done, a failed merge gets a comment of its own
+                let action = match *outcome {
+                    FfcpOutcome::Done => Some(disposition.repr()),
+                    _ => None,
+                };
+
+                let follow_up = follow_up.map(|(follow_up_repo, number)| json!({
+                    "repository": follow_up_repo,
+                    "number": number,
+                    "url": format!("https://github.com/{}/issues/{}", follow_up_repo, number),
+                }));
+
+                templates::render(repo, "fcp-finished", &json!({
+                    "disposition": disposition.repr(),
+                    "review_url": Self::comment_url(issue, status_comment_id),
+                    "action": action,
+                    "merge_pending": *outcome == FfcpOutcome::MergePending,
+                    "is_pull_request": issue.is_pull_request,
+                    "follow_up": follow_up,
+                }))
+            },
+        };
+
+        let mut msg = ok_or!(rendered, why => {
+            error!("Unable to render comment for {}#{}: {:?}", repo, issue.number, why);
+            String::new()
+        });
+
+        // not part of the templates, as it's a note to whoever has to fix things by hand
+        match *comment_type {
+            CommentType::FcpAllReviewedNoConcerns { author, added_label: false, .. } => {
+                Self::couldnt_add_label(&mut msg, issue, author, Label::FCP);
+            }
+            CommentType::FcpWeekPassed { author, added_label: false, .. } => {
+                Self::couldnt_add_label(&mut msg, issue, author, Label::FFCP);
+            }
+            _ => (),
+        }
+
+        msg
+    }
+
+    /// The review checkboxes, concerns and feedback requests of a tracking comment. These are
+    /// read back by `update_proposal_review_status`, so they don't come from a template.
+    fn checklist(issue: &Issue,
+                 teams: &[TaggedTeam],
+                 per_team_quorum: bool,
+                 reviewers: &[(GitHubUser, FcpReviewRequest)],
+                 concerns: &[(GitHubUser, FcpConcern)],
+                 feedback: &[(GitHubUser, FeedbackRequest)])
+                 -> String {
+        let mut msg = Self::reviewer_checklist(teams, per_team_quorum, reviewers);
+
+        if concerns.is_empty() {
+            msg.push_str("\n");
+            msg.push_str(NO_CONCERNS);
+            msg.push_str("\n");
+        } else {
+            msg.push_str("\n");
+            msg.push_str(CONCERNS);
+            msg.push_str("\n\n");
+        }
+
+        for &(_, ref concern) in concerns {
+
+            if let Some(resolved_comment_id) = concern.fk_resolved_comment {
+                msg.push_str("* ~~");
+                msg.push_str(&concern.name);
+                msg.push_str("~~ resolved by ");
+                Self::add_comment_url(issue, &mut msg, resolved_comment_id);
+                msg.push_str("\n");
+
+            } else {
+                msg.push_str("* ");
+                msg.push_str(&concern.name);
+                msg.push_str(" (");
+                Self::add_comment_url(issue, &mut msg, concern.fk_initiating_comment);
+                msg.push_str(")\n");
+            }
+        }
+
+        if !feedback.is_empty() {
+            msg.push_str("\nFeedback requested from:\n\n");
+        }
 
-                msg.push_str(":bell: **This is now entering its final comment period**, ");
-                msg.push_str("as per the [review above](");
-                Self::add_comment_url(issue, &mut msg, status_comment_id);
-                msg.push_str("). :bell:");
+        for &(ref requested, ref request) in feedback {
 
-                if !added_label {
-                    Self::couldnt_add_label(&mut msg, author, Label::FCP);
+            if request.fk_feedback_comment.is_some() {
+                msg.push_str("* [x] @");
+            } else {
+                msg.push_str("* [ ] @");
+            }
+
+            msg.push_str(&requested.login);
+            msg.push('\n');
+        }
+
+        msg
+    }
+
+    /// The reviewers of each team, under the team's name and a mention of its GitHub team so
+    /// that its subscribers are notified. Reviewers on several teams are listed for each of them.
+    ///
+    /// If teams have to reach their quorum on their own, each team's progress is shown too.
+    fn reviewer_checklist(teams: &[TaggedTeam],
+                          per_team_quorum: bool,
+                          reviewers: &[(GitHubUser, FcpReviewRequest)])
+                          -> String {
+        let mut msg = String::new();
+
+        let mut groups = teams.iter()
+            .map(|team| {
+                let mut heading = match team.ping {
+                    Some(ref ping) => format!("**{}** (@{})", team.name, ping),
+                    None => format!("**{}**", team.name),
+                };
+                if per_team_quorum {
+                    let (complete, outstanding) = team.review_counts(reviewers);
+                    heading.push_str(&format!(" ({} of {} reviewed{})",
+                                              complete,
+                                              complete + outstanding,
+                                              if team.quorum_met(reviewers) {
+                                                  ", quorum reached"
+                                              } else {
+                                                  ""
+                                              }));
                 }
+                heading.push(':');
+                let team_reviewers = reviewers.iter()
+                    .filter(|&&(ref member, _)| team.members.contains(member))
+                    .collect::<Vec<_>>();
+
+                (Some(heading), Some(team), team_reviewers)
+            })
+            .collect::<Vec<_>>();
+
+        // e.g. left the team after their review was requested, or no teams given at all
+        let other_reviewers = reviewers.iter()
+            .filter(|&&(ref member, _)| !teams.iter().any(|team| team.members.contains(member)))
+            .collect::<Vec<_>>();
+        if !other_reviewers.is_empty() {
+            let heading = if teams.is_empty() { None } else { Some("Others:".to_string()) };
+            groups.push((heading, None, other_reviewers));
+        }
 
-                msg
+        for (heading, team, team_reviewers) in groups {
+            if team_reviewers.is_empty() {
+                continue;
             }
 
-            CommentType::FcpWeekPassed {
-                author,
-                added_label,
-                status_comment_id,
-                disposition
-            } => {
-                let mut msg = String::new();
-                msg.push_str("The final comment period, with a disposition to **");
-                msg.push_str(disposition.repr());
-                msg.push_str("**, as per the [review above](");
-                Self::add_comment_url(issue, &mut msg, status_comment_id);
-                msg.push_str("), is now **complete**.");
-
-                match disposition {
-                    FcpDisposition::Merge => {}
-                    FcpDisposition::Close if can_ffcp_close(issue) => {
-                        msg.push_str("\n\nBy the power vested in me by Rust, I hereby close this RFC.");
-                    },
-                    FcpDisposition::Postpone if can_ffcp_postpone(issue) => {
-                        msg.push_str("\n\nBy the power vested in me by Rust, I hereby postpone this RFC.");
-                    },
-                    _ => {},
+            if let Some(heading) = heading {
+                if !msg.is_empty() {
+                    msg.push('\n');
+                }
+                msg.push_str(&heading);
+                msg.push_str("\n\n");
+            }
+
+            for &&(ref member, ref review_request) in &team_reviewers {
+
+                // no checkbox for abstentions, so ticking it can't be mistaken for a review
+                if review_request.abstained {
+                    msg.push_str("* ~~@");
+                    msg.push_str(&member.login);
+                    msg.push_str("~~ (abstained)\n");
+                    continue;
                 }
 
-                if !added_label {
-                    Self::couldnt_add_label(&mut msg, author, Label::FFCP);
+                if review_request.reviewed {
+                    msg.push_str("* [x] @");
+                } else {
+                    msg.push_str("* [ ] @");
                 }
 
-                msg
-            },
+                msg.push_str(&member.login);
+                if let Some(team) = team {
+                    msg.push_str(&team.roles(&member.login));
+                }
+                msg.push('\n');
+            }
         }
+
+        msg
+    }
+
+    fn comment_url(issue: &Issue, comment_id: i32) -> String {
+        let mut url = String::new();
+        Self::add_comment_url(issue, &mut url, comment_id);
+        url
     }
 
     fn add_comment_url(issue: &Issue, msg: &mut String, comment_id: i32) {
@@ -1151,6 +2693,11 @@ impl<'a> MscbotComment<'a> {
 mod test {
     use super::*;
 
+    lazy_static! {
+        static ref MENTIONS: Vec<String> = vec!["@mscbot".to_string(),
+                                                "@mscbot-staging".to_string()];
+    }
+
     #[test]
     fn multiple_commands() {
 let text = r#"
@@ -1165,7 +2712,7 @@ foobar
 @mscbot concern foobar
 "#;
 
-        let cmd = MscbotCommand::from_str_all(text).collect::<Vec<_>>();
+        let cmd = MscbotCommand::from_str_all(text, &MENTIONS).collect::<Vec<_>>();
         assert_eq!(cmd, vec![
             MscbotCommand::ResolveConcern("CONCERN_NAME"),
             MscbotCommand::FcpCancel,
@@ -1208,10 +2755,11 @@ somemoretext")
                     let body_no_colon = concat!("@mscbot ", $cmd);
 
                     let with_colon =
-                        ensure_take_singleton(MscbotCommand::from_str_all(body));
+                        ensure_take_singleton(MscbotCommand::from_str_all(body, &MENTIONS));
 
                     let without_colon =
-                        ensure_take_singleton(MscbotCommand::from_str_all(body_no_colon));
+                        ensure_take_singleton(
+                            MscbotCommand::from_str_all(body_no_colon, &MENTIONS));
 
                     assert_eq!(with_colon, without_colon);
                     assert_eq!(with_colon, expected);
@@ -1226,6 +2774,18 @@ somemoretext")
          "pr reviewed", "pr review", "pr reviewing"],
         MscbotCommand::Reviewed);
 
+    test_from_str!(success_fcp_abstain,
+        ["abstain", "abstained", "abstaining", "abstains",
+         "fcp abstain", "fcp abstained", "fcp abstaining", "fcp abstains",
+         "pr abstain", "pr abstained", "pr abstaining", "pr abstains"],
+        MscbotCommand::Abstain);
+
+    test_from_str!(success_fcp_unabstain,
+        ["unabstain", "unabstained", "unabstaining", "unabstains",
+         "fcp unabstain", "fcp unabstained", "fcp unabstaining", "fcp unabstains",
+         "pr unabstain", "pr unabstained", "pr unabstaining", "pr unabstains"],
+        MscbotCommand::Unabstain);
+
     test_from_str!(success_fcp_merge,
         ["merge", "merged", "merging", "merges",
          "fcp merge", "fcp merged", "fcp merging", "fcp merges",
@@ -1247,6 +2807,33 @@ somemoretext")
         justification!(),
         MscbotCommand::FcpPropose(FcpDisposition::Postpone));
 
+    test_from_str!(success_fcp_disposition_merge,
+        ["disposition merge", "disposition merged",
+         "fcp disposition merge", "fcp disposition merging",
+         "pr disposition merge", "pr disposition merges"],
+        justification!(),
+        MscbotCommand::FcpChangeDisposition(FcpDisposition::Merge));
+
+    test_from_str!(success_fcp_disposition_close,
+        ["disposition close", "fcp disposition close", "pr disposition closed"],
+        justification!(),
+        MscbotCommand::FcpChangeDisposition(FcpDisposition::Close));
+
+    test_from_str!(success_fcp_disposition_postpone,
+        ["disposition postpone", "fcp disposition postpone", "pr disposition postponed"],
+        justification!(),
+        MscbotCommand::FcpChangeDisposition(FcpDisposition::Postpone));
+
+    #[test]
+    fn fail_fcp_disposition_unknown() {
+        assert!(MscbotCommand::from_str_all("@mscbot fcp disposition maybe", &MENTIONS)
+                    .next()
+                    .is_none());
+        assert!(MscbotCommand::from_str_all("@mscbot fcp disposition", &MENTIONS)
+                    .next()
+                    .is_none());
+    }
+
     test_from_str!(success_fcp_cancel,
         ["cancel", "canceled", "canceling", "cancels",
          "fcp cancel", "fcp canceled", "fcp canceling", "fcp cancels",
@@ -1282,9 +2869,9 @@ somemoretext
 
 somemoretext";
 
-        let with_colon = ensure_take_singleton(MscbotCommand::from_str_all(body));
+        let with_colon = ensure_take_singleton(MscbotCommand::from_str_all(body, &MENTIONS));
         let without_colon =
-            ensure_take_singleton(MscbotCommand::from_str_all(body_no_colon));
+            ensure_take_singleton(MscbotCommand::from_str_all(body_no_colon, &MENTIONS));
 
         assert_eq!(with_colon, without_colon);
         assert_eq!(with_colon, MscbotCommand::ResolveConcern("CONCERN_NAME"));
@@ -1292,4 +2879,219 @@ somemoretext";
 
     test_from_str!(success_feedback, ["f?"], some_text!("@bob"),
         MscbotCommand::FeedbackRequest("bob"));
+
+    fn parse_error(body: &str) -> String {
+        let (_, parsed) = ensure_take_singleton(MscbotCommand::parse_all(body, &MENTIONS));
+        match parsed {
+            Err(DashError::Rejected(reason)) => reason,
+            other => panic!("expected an explained parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn fail_explained() {
+        assert_eq!(parse_error("@mscbot fcp mrege"),
+                   "unknown subcommand `mrege`, did you mean `merge`?");
+        assert_eq!(parse_error("@mscbot revewed"),
+                   "unknown command `revewed`, did you mean `reviewed`?");
+        assert_eq!(parse_error("@mscbot: thank you"), "unknown command `thank`");
+        assert_eq!(parse_error("@mscbot fcp disposition clsoe"),
+                   "unknown disposition `clsoe`, did you mean `close`?");
+        assert_eq!(parse_error("@mscbot"), "there's no command after the mention");
+        assert_eq!(parse_error("@mscbot fcp"), "`fcp` needs a subcommand, e.g. `fcp merge`");
+        assert_eq!(parse_error("@mscbot concern"), "a concern needs a name");
+        assert!(parse_error("@mscbot f?").starts_with("`f?` needs a user"));
+        assert!(parse_error("@mscbot f? bob").starts_with("`f?` needs a user"));
+    }
+
+    #[test]
+    fn mention_aliases() {
+        let text = "@mscbot-staging fcp merge\n@mscbot: reviewed\n@mscbotty concern nope";
+        assert_eq!(MscbotCommand::from_str_all(text, &MENTIONS).collect::<Vec<_>>(),
+                   vec![MscbotCommand::FcpPropose(FcpDisposition::Merge),
+                        MscbotCommand::Reviewed]);
+
+        // not a mention of ours, so not even a malformed command
+        assert_eq!(MscbotCommand::parse_all("@mscbotty concern nope", &MENTIONS).count(), 0);
+        assert_eq!(diff_command_lines("@mscbot-staging reviewed",
+                                      "",
+                                      &["@mscbot".to_string()]),
+                   (vec![], vec![]));
+    }
+
+    fn user(id: i32, login: &str) -> GitHubUser {
+        GitHubUser { id: id, login: login.to_string() }
+    }
+
+    fn review(user: &GitHubUser, reviewed: bool, abstained: bool)
+              -> (GitHubUser, FcpReviewRequest) {
+        (user.clone(), FcpReviewRequest {
+            id: user.id,
+            fk_proposal: 1,
+            fk_reviewer: user.id,
+            reviewed: reviewed,
+            abstained: abstained,
+            active: true,
+        })
+    }
+
+    /// A tagged team without any rules, which the tests adjust as they need.
+    fn team(name: &str, members: &[&GitHubUser]) -> TaggedTeam {
+        TaggedTeam {
+            name: name.to_string(),
+            ping: None,
+            members: members.iter().map(|&member| member.clone()).collect(),
+            leads: vec![],
+            required_reviewers: vec![],
+            min_reviewers: None,
+            quorum: QuorumPolicy::Rfcbot,
+        }
+    }
+
+    #[test]
+    fn reviewers_grouped_by_team() {
+        let (alice, bob, carol, dave) = (user(1, "alice"), user(2, "bob"), user(3, "carol"),
+                                         user(4, "dave"));
+        let core = TaggedTeam {
+            ping: Some("org/core".to_string()),
+            leads: vec!["alice".to_string()],
+            required_reviewers: vec!["alice".to_string(), "bob".to_string()],
+            ..team("Core team", &[&alice, &bob])
+        };
+        let teams = [core, team("T-spec", &[&bob, &carol])];
+        let reviewers = [review(&alice, true, false),
+                         review(&bob, false, false),
+                         review(&carol, false, true),
+                         review(&dave, false, false)];
+
+        assert_eq!(MscbotComment::reviewer_checklist(&teams, false, &reviewers),
+                   "**Core team** (@org/core):\n\n* [x] @alice (lead, required)\n\
+                    * [ ] @bob (required)\n\n\
+                    **T-spec**:\n\n* [ ] @bob\n* ~~@carol~~ (abstained)\n\n\
+                    Others:\n\n* [ ] @dave\n");
+
+        // each team has to reach its quorum on its own
+        let mut teams = teams;
+        teams[1].quorum = QuorumPolicy::Unanimous;
+        assert_eq!(teams[0].review_counts(&reviewers), (1, 1));
+        assert_eq!(teams[1].review_counts(&reviewers), (0, 1));
+        assert!(teams[0].quorum_met(&reviewers[..1]));
+        assert!(!teams[1].quorum_met(&reviewers));
+        assert_eq!(MscbotComment::reviewer_checklist(&teams, true, &reviewers),
+                   "**Core team** (@org/core) (1 of 2 reviewed):\n\n\
+                    * [x] @alice (lead, required)\n* [ ] @bob (required)\n\n\
+                    **T-spec** (0 of 1 reviewed):\n\n* [ ] @bob\n* ~~@carol~~ (abstained)\n\n\
+                    Others:\n\n* [ ] @dave\n");
+
+        // a team with nobody left to review can't hold the proposal up
+        let abstained = [review(&bob, false, true), review(&carol, false, true)];
+        assert_eq!(teams[1].review_counts(&abstained), (0, 0));
+        assert!(teams[1].quorum_met(&abstained));
+        let unfetched = TaggedTeam { quorum: QuorumPolicy::Unanimous, ..team("T-lang", &[]) };
+        assert!(unfetched.quorum_met(&reviewers));
+
+        // without teams, there's nothing to group by
+        assert_eq!(MscbotComment::reviewer_checklist(&[], false, &reviewers[..2]),
+                   "* [x] @alice\n* [ ] @bob\n");
+    }
+
+    #[test]
+    fn team_rules() {
+        let (alice, bob, carol) = (user(1, "alice"), user(2, "bob"), user(3, "carol"));
+        let reviews = |reviewed: &[&GitHubUser], abstained: &[&GitHubUser]| {
+            [&alice, &bob, &carol].iter()
+                .map(|&member| {
+                    review(member, reviewed.contains(&member), abstained.contains(&member))
+                })
+                .collect::<Vec<_>>()
+        };
+        let core_team = |leads: &[&str], required: &[&str], min: Option<usize>| TaggedTeam {
+            leads: leads.iter().map(|l| l.to_string()).collect(),
+            required_reviewers: required.iter().map(|r| r.to_string()).collect(),
+            min_reviewers: min,
+            ..team("Core team", &[&alice, &bob, &carol])
+        };
+
+        assert!(core_team(&[], &[], None).rules_met(&reviews(&[], &[])));
+
+        let leads = core_team(&["alice", "bob"], &[], None);
+        assert!(!leads.rules_met(&reviews(&[&carol], &[])));
+        assert!(leads.rules_met(&reviews(&[&bob, &carol], &[])));
+
+        let required = core_team(&[], &["alice", "carol"], None);
+        assert!(!required.rules_met(&reviews(&[&alice, &bob], &[])));
+        assert!(required.rules_met(&reviews(&[&alice, &carol], &[])));
+
+        let min = core_team(&[], &[], Some(2));
+        assert!(!min.rules_met(&reviews(&[&bob], &[])));
+        assert!(min.rules_met(&reviews(&[&bob, &carol], &[])));
+
+        // abstaining releases members from the rules, instead of holding the proposal up
+        assert!(!leads.rules_met(&reviews(&[&carol], &[&alice])));
+        assert!(leads.rules_met(&reviews(&[&carol], &[&alice, &bob])));
+        assert!(!required.rules_met(&reviews(&[], &[&carol])));
+        assert!(required.rules_met(&reviews(&[&alice], &[&carol])));
+        assert!(!min.rules_met(&reviews(&[], &[&alice])));
+        assert!(min.rules_met(&reviews(&[&bob], &[&alice, &carol])));
+        assert!(core_team(&["alice"], &["bob"], Some(3))
+                    .rules_met(&reviews(&[&carol], &[&alice, &bob])));
+
+        // e.g. the lead or required reviewer was renamed on GitHub
+        assert!(core_team(&["zed"], &["yan"], None).rules_met(&reviews(&[], &[])));
+
+        assert_eq!(core_team(&["alice"], &["bob", "carol"], Some(2)).rule_descriptions(),
+                   vec!["a lead of Core team approves",
+                        "@bob, @carol approve",
+                        "at least 2 members of Core team approve"]);
+    }
+
+    #[test]
+    fn ticked_boxes_are_reviews() {
+        let body = "**Core team** (@org/core):\n\n* [x] @alice (lead)\n* [ ] @bob\n\
+                    * ~~@carol~~ (abstained)\n* [x] @dave\n\n\
+                    No concerns currently listed.\n\nFeedback requested from:\n\n* [x] @erin\n";
+        assert_eq!(ticked_reviewers(body), vec!["alice", "dave"]);
+
+        // e.g. the box was ticked before the tracking comment caught up with an abstention
+        let (_, mut abstained) = review(&user(1, "alice"), false, true);
+        assert!(mark_reviewed(&mut abstained));
+        assert!(abstained.reviewed);
+        assert!(!abstained.abstained);
+        assert!(!mark_reviewed(&mut abstained));
+    }
+
+    #[test]
+    fn edited_command_lines() {
+        let previous = "@mscbot concern speling\n\nSome text.\n@mscbot reviewed";
+        let current = "@mscbot concern spelling\n\nSome other text.\n@mscbot reviewed\n";
+        assert_eq!(diff_command_lines(previous, current, &MENTIONS),
+                   (vec!["@mscbot concern spelling"], vec!["@mscbot concern speling"]));
+
+        assert_eq!(diff_command_lines(previous, "Not a command anymore.", &MENTIONS),
+                   (vec![], vec!["@mscbot concern speling", "@mscbot reviewed"]));
+
+        assert_eq!(diff_command_lines("Just text.", "Still just text, edited.", &MENTIONS),
+                   (vec![], vec![]));
+    }
+
+    #[test]
+    fn edit_distances() {
+        assert_eq!(edit_distance("merge", "merge"), 0);
+        assert_eq!(edit_distance("mrege", "merge"), 2);
+        assert_eq!(edit_distance("concer", "concern"), 1);
+        assert_eq!(edit_distance("", "close"), 5);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn fcp_elapsed_at_end() {
+        let start = Utc::now().naive_utc();
+        let end = start + Duration::days(10);
+
+        assert!(!fcp_has_elapsed(None, start));
+        assert!(!fcp_has_elapsed(Some(end), start));
+        assert!(!fcp_has_elapsed(Some(end), start + Duration::days(9)));
+        assert!(fcp_has_elapsed(Some(end), end));
+        assert!(fcp_has_elapsed(Some(end), start + Duration::days(11)));
+    }
 }
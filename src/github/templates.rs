@@ -0,0 +1,200 @@
+//! Handlebars templates for the comments mscbot posts.
+//!
+//! Each repo can override any of them with `[fcp_behaviors."org/repo".comments]` in
+//! `mscbot.toml`, e.g. to use its own wording and link to its own documentation.
+
+use handlebars::{self, Handlebars, Template};
+use serde_json::Value;
+
+use error::{DashError, DashResult};
+use teams::SETUP;
+
+/// The names of all comment templates, as used in `mscbot.toml`.
+pub const TEMPLATE_NAMES: &'static [&'static str] = &[
+    "fcp-proposed",
+    "fcp-cancelled",
+    "command-rejected",
+    "merge-failed",
+    "disposition-changed",
+    "fcp-started",
+    "fcp-finished",
+];
+
+fn default_template(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "fcp-proposed" => include_str!("../templates/comments/fcp-proposed.hbs"),
+        "fcp-cancelled" => include_str!("../templates/comments/fcp-cancelled.hbs"),
+        "command-rejected" => include_str!("../templates/comments/command-rejected.hbs"),
+        "merge-failed" => include_str!("../templates/comments/merge-failed.hbs"),
+        "disposition-changed" => include_str!("../templates/comments/disposition-changed.hbs"),
+        "fcp-started" => include_str!("../templates/comments/fcp-started.hbs"),
+        "fcp-finished" => include_str!("../templates/comments/fcp-finished.hbs"),
+        _ => return None,
+    })
+}
+
+/// Check a template from `mscbot.toml` before we try to use it.
+pub fn validate(name: &str, template: &str) -> DashResult<()> {
+    if default_template(name).is_none() {
+        throw!(DashError::Misc(Some(format!("unknown comment template `{}`, expected one of {}",
+                                            name,
+                                            TEMPLATE_NAMES.join(", ")))));
+    }
+
+    if let Err(why) = Template::compile(template) {
+        throw!(DashError::Misc(Some(format!("comment template `{}` is invalid: {}",
+                                            name, why))));
+    }
+
+    // we read the review checkboxes back from the tracking comment
+    if name == "fcp-proposed" && !template.contains("{{checklist}}") {
+        throw!(DashError::Misc(Some("comment template `fcp-proposed` must contain \
+                                     `{{checklist}}`".to_string())));
+    }
+
+    Ok(())
+}
+
+/// Render the comment template `name` for an issue in `repo`, preferring the repo's own
+/// version of it.
+pub fn render(repo: &str, name: &str, data: &Value) -> DashResult<String> {
+    let default = default_template(name).ok_or_else(|| {
+        DashError::Misc(Some(format!("unknown comment template `{}`", name)))
+    })?;
+
+    let setup = SETUP.get();
+    if let Some(template) = setup.comment_template(repo, name) {
+        match render_template(template, data) {
+            Ok(rendered) => return Ok(rendered),
+            Err(why) => {
+                error!("Unable to render {}'s `{}` comment template, using the default: {:?}",
+                       repo, name, why)
+            }
+        }
+    }
+
+    render_template(default, data)
+}
+
+fn render_template(template: &str, data: &Value) -> DashResult<String> {
+    let mut hbars = Handlebars::new();
+    // comments are markdown, not HTML
+    hbars.register_escape_fn(handlebars::no_escape);
+
+    Ok(tidy(&hbars.template_render(template, data)?))
+}
+
+/// Trim a rendered comment and collapse runs of blank lines, so that templates can put
+/// `{{#if}}` blocks on lines of their own.
+fn tidy(rendered: &str) -> String {
+    let mut tidied = String::with_capacity(rendered.len());
+    let mut newlines = 0;
+
+    for c in rendered.trim().chars() {
+        if c == '\n' {
+            newlines += 1;
+            if newlines > 2 {
+                continue;
+            }
+        } else {
+            newlines = 0;
+        }
+        tidied.push(c);
+    }
+
+    tidied
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn render_default(name: &str, data: Value) -> String {
+        render_template(default_template(name).unwrap(), &data).unwrap()
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        for name in TEMPLATE_NAMES {
+            validate(name, default_template(name).unwrap()).unwrap();
+        }
+    }
+
+    #[test]
+    fn invalid_overrides() {
+        assert!(validate("fcp-closed", "Closed.").is_err());
+        assert!(validate("fcp-finished", "{{#if action}}Done.").is_err());
+        assert!(validate("fcp-proposed", "Please review.").is_err());
+        assert!(validate("fcp-proposed", "Please review:\n\n{{checklist}}").is_ok());
+    }
+
+    #[test]
+    fn tidied() {
+        assert_eq!(tidy("\n  a\n\n\n\nb\n\nc\nd\n\n"), "a\n\nb\n\nc\nd");
+    }
+
+    #[test]
+    fn fcp_finished() {
+        let review_url = "https://github.com/org/repo/issues/1#issuecomment-2";
+
+        assert_eq!(render_default("fcp-finished", json!({
+                       "disposition": "close",
+                       "review_url": review_url,
+                       "action": "close",
+                       "is_pull_request": false,
+                   })),
+                   format!("The final comment period, with a disposition to **close**, as per \
+                            the [review above]({}), is now **complete**.\n\nAccordingly, I \
+                            hereby close this issue.",
+                           review_url));
+
+        assert_eq!(render_default("fcp-finished", json!({
+                       "disposition": "merge",
+                       "review_url": review_url,
+                       "action": null,
+                       "follow_up": {
+                           "repository": "org/spec",
+                           "number": 3,
+                           "url": "https://github.com/org/spec/issues/3",
+                       },
+                   })),
+                   format!("The final comment period, with a disposition to **merge**, as per \
+                            the [review above]({}), is now **complete**.\n\nThe next steps are \
+                            tracked in [org/spec#3](https://github.com/org/spec/issues/3).",
+                           review_url));
+
+        assert_eq!(render_default("fcp-finished", json!({
+                       "disposition": "merge",
+                       "review_url": review_url,
+                       "action": null,
+                       "merge_pending": true,
+                       "is_pull_request": true,
+                   })),
+                   format!("The final comment period, with a disposition to **merge**, as per \
+                            the [review above]({}), is now **complete**.\n\nI'll merge this pull \
+                            request as soon as it's ready, e.g. once its checks have finished.",
+                           review_url));
+    }
+
+    #[test]
+    fn disposition_changed() {
+        assert_eq!(render_default("disposition-changed", json!({
+                       "author": "bob",
+                       "old_disposition": "merge",
+                       "new_disposition": "close",
+                       "reviews_reset": true,
+                       "left_fcp": false,
+                   })),
+                   "@bob changed the disposition of this proposal from **merge** to **close**. \
+                    Reviews have been reset, so the tagged teams need to review it again.");
+    }
+
+    #[test]
+    fn no_html_escaping() {
+        assert_eq!(render_default("command-rejected", json!({
+                       "author": "bob",
+                       "reason": "unknown command `<b>`",
+                   })),
+                   "@bob, unknown command `<b>`");
+    }
+}
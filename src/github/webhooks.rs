@@ -154,6 +154,18 @@ pub enum Payload {
     Unsupported,
 }
 
+impl Payload {
+    /// The `org/repo` the event happened in, if it's one we handle.
+    pub fn repository(&self) -> Option<&str> {
+        match *self {
+            Payload::Issues(ref event) => Some(&event.repository.full_name),
+            Payload::IssueComment(ref event) => Some(&event.repository.full_name),
+            Payload::PullRequest(ref event) => Some(&event.repository.full_name),
+            Payload::Unsupported => None,
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct IssuesEvent {
     pub action: String,
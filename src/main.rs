@@ -16,6 +16,7 @@ extern crate hyper;
 extern crate hyper_native_tls;
 #[macro_use]
 extern crate lazy_static;
+extern crate libc;
 #[macro_use]
 extern crate log;
 extern crate r2d2;
@@ -36,6 +37,7 @@ extern crate maplit;
 #[macro_use]
 mod macros;
 
+mod cli;
 mod config;
 mod domain;
 mod error;
@@ -52,6 +54,7 @@ use log::LogRecord;
 use r2d2::Pool;
 use r2d2_diesel::ConnectionManager;
 
+use cli::Command;
 use config::CONFIG;
 
 fn main() {
@@ -73,15 +76,49 @@ fn main() {
         .unwrap();
 
     debug!("Logging initialized.");
+
+    let command = match Command::from_args(std::env::args().skip(1)) {
+        Ok(command) => command,
+        Err(why) => {
+            eprintln!("{}\n\n{}", why, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    if command == Command::Help {
+        print!("{}", cli::USAGE);
+        return;
+    }
+
     let _ = CONFIG.check();
     let _ = DB_POOL.get().expect("Unable to test connection pool.");
 
-    // we want to panic if we're unable to find any of the usernames
-    let parsed_teams = teams::SETUP.team_labels().collect::<Vec<_>>();
+    if command != Command::Serve {
+        ok_or!(command.run(), why => {
+            error!("{:?}", why);
+            std::process::exit(1);
+        });
+        return;
+    }
+
+    serve();
+}
+
+/// Run the scraper, the nag evaluation and the web server until we're killed.
+fn serve() {
+    // loading mscbot.toml panics if it's invalid, e.g. if a team member isn't on GitHub
+    let setup = teams::SETUP.get();
+    let parsed_teams = setup.team_labels().collect::<Vec<_>>();
     info!("parsed teams: {:?}", parsed_teams);
 
+    // people may have joined or left teams since we last ran
+    ok_or!(github::reconcile_review_requests(), why =>
+        error!("Unable to reconcile review requests with team membership: {:?}", why));
+
     // FIXME(anp) need to handle panics in both the listeners and crash the server
     let _ = scraper::start_scraping();
+    let _ = scraper::start_evaluating_nags();
+    let _ = scraper::start_reloading_on_hangup();
     let _server_handle = server::serve();
 
     // block
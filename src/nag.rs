@@ -2,24 +2,27 @@ use diesel::prelude::*;
 
 use DB_POOL;
 use domain::github::{GitHubUser, Issue, IssueComment};
-use domain::mscbot::{FcpProposal, FcpReviewRequest};
+use domain::mscbot::{FcpProposal, FcpReviewRequest, FcpState, FcpStateTransition,
+                     FeedbackRequest};
 use error::DashResult;
 
 #[derive(Serialize)]
 pub struct FcpWithInfo {
     pub fcp: FcpProposal,
     pub reviews: Vec<(GitHubUser, bool)>,
+    pub feedback: Vec<(GitHubUser, bool)>,
     pub issue: Issue,
     pub status_comment: IssueComment,
 }
 
 pub fn all_fcps() -> DashResult<Vec<FcpWithInfo>> {
-    use domain::schema::{fcp_proposal, fcp_review_request, githubuser, issue, issuecomment};
+    use domain::schema::{fcp_proposal, fcp_review_request, githubuser, issue, issuecomment,
+                         rfc_feedback_request};
 
     let conn = &*DB_POOL.get()?;
 
     let proposals = fcp_proposal::table
-        .filter(fcp_proposal::fcp_start.is_null())
+        .filter(fcp_proposal::state.eq(FcpState::Proposed.repr()))
         .load::<FcpProposal>(conn)?;
 
     let mut all_fcps = Vec::new();
@@ -27,17 +30,32 @@ pub fn all_fcps() -> DashResult<Vec<FcpWithInfo>> {
     for fcp in proposals {
         let reviews = fcp_review_request::table
             .filter(fcp_review_request::fk_proposal.eq(fcp.id))
+            .filter(fcp_review_request::active.eq(true))
             .load::<FcpReviewRequest>(conn)?;
 
         let mut reviews_with_users = Vec::new();
 
-        for review in reviews {
+        // abstaining members aren't waited on, so leave them out entirely
+        for review in reviews.into_iter().filter(|r| !r.abstained) {
             let user = githubuser::table
                 .filter(githubuser::id.eq(review.fk_reviewer))
                 .first(conn)?;
             reviews_with_users.push((user, review.reviewed));
         }
 
+        let feedback_requests = rfc_feedback_request::table
+            .filter(rfc_feedback_request::fk_issue.eq(fcp.fk_issue))
+            .load::<FeedbackRequest>(conn)?;
+
+        let mut feedback_with_users = Vec::new();
+
+        for request in feedback_requests {
+            let user = githubuser::table
+                .filter(githubuser::id.eq(request.fk_requested))
+                .first(conn)?;
+            feedback_with_users.push((user, request.fk_feedback_comment.is_some()));
+        }
+
         let status_comment = issuecomment::table
             .filter(issuecomment::id.eq(fcp.fk_bot_tracking_comment))
             .first::<IssueComment>(conn)?;
@@ -49,6 +67,7 @@ pub fn all_fcps() -> DashResult<Vec<FcpWithInfo>> {
         let fcp_with_info = FcpWithInfo {
             fcp: fcp,
             reviews: reviews_with_users,
+            feedback: feedback_with_users,
             issue: issue,
             status_comment: status_comment,
         };
@@ -66,6 +85,13 @@ pub struct IndividualFcp {
     review_request: FcpReviewRequest,
 }
 
+#[derive(Serialize)]
+pub struct IndividualFeedbackRequest {
+    issue: Issue,
+    initiator: GitHubUser,
+    request: FeedbackRequest,
+}
+
 pub fn individual_nags(username: &str) -> DashResult<(GitHubUser, Vec<IndividualFcp>)> {
     use domain::schema::{fcp_proposal, fcp_review_request, githubuser, issue};
     let conn = &*DB_POOL.get()?;
@@ -76,9 +102,11 @@ pub fn individual_nags(username: &str) -> DashResult<(GitHubUser, Vec<Individual
 
     let review_requests = fcp_review_request::table
         .inner_join(fcp_proposal::table)
-            .filter(fcp_proposal::fcp_start.is_null())
+            .filter(fcp_proposal::state.eq(FcpState::Proposed.repr()))
         .filter(fcp_review_request::fk_reviewer.eq(user.id))
         .filter(fcp_review_request::reviewed.eq(false))
+        .filter(fcp_review_request::abstained.eq(false))
+        .filter(fcp_review_request::active.eq(true))
         .load::<(FcpReviewRequest, FcpProposal)>(conn)?;
 
     let mut fcps = Vec::new();
@@ -96,3 +124,109 @@ pub fn individual_nags(username: &str) -> DashResult<(GitHubUser, Vec<Individual
 
     Ok((user, fcps))
 }
+
+/// The feedback still requested of a user, on issues which are still open.
+pub fn individual_feedback_requests(
+    username: &str,
+) -> DashResult<Vec<IndividualFeedbackRequest>> {
+    use domain::schema::{githubuser, issue, rfc_feedback_request};
+    let conn = &*DB_POOL.get()?;
+
+    let user = githubuser::table
+        .filter(githubuser::login.eq(username))
+        .first::<GitHubUser>(conn)?;
+
+    let feedback_requests = rfc_feedback_request::table
+        .inner_join(issue::table)
+        .filter(issue::open.eq(true))
+        .filter(rfc_feedback_request::fk_requested.eq(user.id))
+        .filter(rfc_feedback_request::fk_feedback_comment.is_null())
+        .load::<(FeedbackRequest, Issue)>(conn)?;
+
+    let mut feedback = Vec::new();
+    for (request, issue) in feedback_requests {
+        let initiator = githubuser::table
+            .filter(githubuser::id.eq(request.fk_initiator))
+            .first::<GitHubUser>(conn)?;
+
+        feedback.push(IndividualFeedbackRequest {
+            issue: issue,
+            initiator: initiator,
+            request: request,
+        });
+    }
+
+    Ok(feedback)
+}
+
+#[derive(Serialize)]
+pub struct ProposalHistory {
+    pub fcp: FcpProposal,
+    pub initiator: GitHubUser,
+    pub cancelled_by: Option<GitHubUser>,
+    pub transitions: Vec<FcpStateTransition>,
+    pub reviews: Vec<(GitHubUser, FcpReviewRequest)>,
+}
+
+#[derive(Serialize)]
+pub struct IssueHistory {
+    pub issue: Issue,
+    pub proposals: Vec<ProposalHistory>,
+}
+
+/// Every proposal ever made on an issue, including cancelled ones, oldest first.
+pub fn proposal_history(repo: &str, number: i32) -> DashResult<IssueHistory> {
+    use domain::schema::{fcp_proposal, fcp_review_request, fcp_state_transition, githubuser,
+                         issue};
+    let conn = &*DB_POOL.get()?;
+
+    let issue = issue::table
+        .filter(issue::repository.eq(repo))
+        .filter(issue::number.eq(number))
+        .first::<Issue>(conn)?;
+
+    let fcps = fcp_proposal::table
+        .filter(fcp_proposal::fk_issue.eq(issue.id))
+        .order(fcp_proposal::id)
+        .load::<FcpProposal>(conn)?;
+
+    let mut proposals = Vec::new();
+    for fcp in fcps {
+        let initiator = githubuser::table
+            .find(fcp.fk_initiator)
+            .first::<GitHubUser>(conn)?;
+
+        let cancelled_by = match fcp.fk_cancelled_by {
+            Some(user_id) => Some(githubuser::table.find(user_id).first::<GitHubUser>(conn)?),
+            None => None,
+        };
+
+        let transitions = fcp_state_transition::table
+            .filter(fcp_state_transition::fk_proposal.eq(fcp.id))
+            .order(fcp_state_transition::id)
+            .load::<FcpStateTransition>(conn)?;
+
+        let reviews = fcp_review_request::table
+            .inner_join(githubuser::table)
+            .filter(fcp_review_request::fk_proposal.eq(fcp.id))
+            .filter(fcp_review_request::active.eq(true))
+            .order(githubuser::login)
+            .load::<(FcpReviewRequest, GitHubUser)>(conn)?
+            .into_iter()
+            .map(|(review, user)| (user, review))
+            .collect();
+
+        proposals.push(ProposalHistory {
+            fcp: fcp,
+            initiator: initiator,
+            cancelled_by: cancelled_by,
+            transitions: transitions,
+            reviews: reviews,
+        });
+    }
+
+    Ok(IssueHistory {
+        issue: issue,
+        proposals: proposals,
+    })
+}
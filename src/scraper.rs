@@ -1,17 +1,25 @@
+use std::sync::atomic::{AtomicBool, Ordering, ATOMIC_BOOL_INIT};
 use std::thread::{spawn, JoinHandle};
 use std::thread;
 use std::time::Duration;
 
 use chrono::{DateTime, Utc};
+use libc;
 
-use config::{CONFIG, GH_ORGS};
+use config::CONFIG;
 use github;
+use teams::{self, SETUP};
+
+/// Set by the SIGHUP handler, which can't do much more than that.
+static HANGUP_RECEIVED: AtomicBool = ATOMIC_BOOL_INIT;
 
 pub fn start_scraping() -> JoinHandle<()> {
     // spawn the github scraper in the background
     spawn(|| {
         let sleep_duration = Duration::from_secs(CONFIG.github_interval_mins * 60);
         loop {
+            refresh_team_members();
+
             match github::most_recent_update() {
                 Ok(gh_most_recent) => scrape_github(gh_most_recent),
                 Err(why) => error!("Unable to determine most recent GH update: {:?}", why),
@@ -24,13 +32,66 @@ pub fn start_scraping() -> JoinHandle<()> {
     })
 }
 
+pub fn start_evaluating_nags() -> JoinHandle<()> {
+    // FCPs end with the passage of time, not only when someone comments
+    spawn(|| {
+        let sleep_duration = Duration::from_secs(CONFIG.nag_interval_mins * 60);
+        loop {
+            thread::sleep(sleep_duration);
+            debug!("Evaluating outstanding proposals");
+            ok_or!(github::evaluate_all_nags(), why =>
+                error!("Unable to evaluate outstanding proposals: {:?}", why));
+        }
+    })
+}
+
+extern "C" fn on_hangup(_signal: libc::c_int) {
+    HANGUP_RECEIVED.store(true, Ordering::SeqCst);
+}
+
+pub fn start_reloading_on_hangup() -> JoinHandle<()> {
+    // reload mscbot.toml on SIGHUP, like most daemons do with their config
+    let handler = on_hangup as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    if unsafe { libc::signal(libc::SIGHUP, handler) } == libc::SIG_ERR {
+        error!("Unable to install SIGHUP handler, mscbot.toml can't be reloaded with it");
+    }
+
+    spawn(|| {
+        let sleep_duration = Duration::from_secs(1);
+        loop {
+            thread::sleep(sleep_duration);
+            if HANGUP_RECEIVED.swap(false, Ordering::SeqCst) {
+                info!("Received SIGHUP, reloading {}", CONFIG.mscbot_config_path);
+                ok_or!(teams::reload_setup(), why =>
+                    error!("Unable to reload {}, keeping the previous configuration: {:?}",
+                           CONFIG.mscbot_config_path, why));
+            }
+        }
+    })
+}
+
+/// Fetch the members of GitHub teams, and update who has to review pending proposals if they
+/// changed.
+pub fn refresh_team_members() {
+    match github::refresh_team_members() {
+        Ok(true) => {
+            ok_or!(github::reconcile_review_requests(), why =>
+                error!("Unable to reconcile review requests with team membership: {:?}", why));
+        }
+        Ok(false) => (),
+        Err(why) => error!("Unable to refresh GitHub team members: {:?}", why),
+    }
+}
+
 pub fn scrape_github(since: DateTime<Utc>) {
+    let setup = SETUP.get();
     let mut repos = Vec::new();
-    for org in &GH_ORGS {
-        repos.extend(ok_or!(github::GH.org_repos(org), why => {
+    for org in setup.orgs() {
+        let org_repos = ok_or!(github::GH.org_repos(org), why => {
             error!("Unable to retrieve repos for {}: {:?}", org, why);
             return;
-        }));
+        });
+        repos.extend(org_repos.into_iter().filter(|repo| setup.tracks_repo(repo)));
     }
 
     info!("Scraping github activity since {:?}", since);
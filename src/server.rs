@@ -13,9 +13,19 @@ pub fn serve() {
             rocket::ignite()
                 .mount(
                     "/api",
-                    routes![api::all_fcps, api::member_fcps, api::github_webhook],
+                    routes![
+                        api::all_fcps,
+                        api::member_fcps,
+                        api::member_feedback,
+                        api::proposal_history,
+                        api::github_webhook,
+                        api::reload_config
+                    ],
+                )
+                .mount(
+                    "/",
+                    routes![html::all_fcps, html::member_fcps, html::proposal_history],
                 )
-                .mount("/", routes![html::all_fcps, html::member_fcps])
                 .launch();
         });
 
@@ -37,6 +47,7 @@ mod html {
             let nag::FcpWithInfo {
                 fcp,
                 reviews,
+                feedback,
                 issue,
                 status_comment,
             } = fcp;
@@ -49,11 +60,20 @@ mod html {
 
             pending_reviewers.sort();
 
+            let mut pending_feedback = feedback
+                .into_iter()
+                .filter(|&(_, responded)| !responded)
+                .map(|(user, _)| user.login)
+                .collect::<Vec<String>>();
+
+            pending_feedback.sort();
+
             let record = json!({
                 "disposition": fcp.disposition,
                 "issue": issue,
                 "statusComment": status_comment,
                 "pendingReviewers": pending_reviewers,
+                "pendingFeedback": pending_feedback,
             });
 
             for label in issue.labels.iter().filter(|l| l.starts_with("T-")).cloned() {
@@ -81,27 +101,85 @@ mod html {
     #[get("/fcp/<username>")]
     fn member_fcps(username: String) -> DashResult<content::Html<String>> {
         let (user, fcps) = nag::individual_nags(&username)?;
+        let feedback = nag::individual_feedback_requests(&username)?;
 
         let context = json!({
             "model": {
                 "user": user,
                 "fcps": fcps,
+                "feedback": feedback,
             }
         });
 
         let rendered = TEMPLATES.render("user", &context)?;
         Ok(content::Html(rendered))
     }
+
+    #[get("/history/<owner>/<repo>/<number>")]
+    fn proposal_history(
+        owner: String,
+        repo: String,
+        number: i32,
+    ) -> DashResult<content::Html<String>> {
+        let history = nag::proposal_history(&format!("{}/{}", owner, repo), number)?;
+
+        let rendered = TEMPLATES.render("history", &json!({ "model": history }))?;
+        Ok(content::Html(rendered))
+    }
 }
 
 mod api {
+    use crypto::util::fixed_time_eq;
+    use rocket::http::Status;
+    use rocket::outcome::Outcome::*;
+    use rocket::request::{self, FromRequest, Request};
+    use rocket::response::status;
     use rocket_contrib::Json;
     use DB_POOL;
+    use config::CONFIG;
     use domain::github::GitHubUser;
     use error::DashResult;
-    use github::{handle_comment, handle_issue, handle_pr};
+    use github::{handle_comment, handle_comment_deleted, handle_issue, handle_pr};
     use github::webhooks::{Event, Payload};
     use nag;
+    use teams::{self, SETUP};
+
+    /// A request carrying `Authorization: token ADMIN_TOKEN`.
+    pub struct Admin;
+
+    impl<'a, 'r> FromRequest<'a, 'r> for Admin {
+        type Error = &'static str;
+        fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, Self::Error> {
+            let token = match CONFIG.admin_token {
+                Some(ref token) => format!("token {}", token),
+                None => return Failure((Status::NotFound, "admin endpoints are disabled")),
+            };
+
+            match request.headers().get_one("Authorization") {
+                Some(auth) if auth.len() == token.len() &&
+                              fixed_time_eq(auth.as_bytes(), token.as_bytes()) => Success(Admin),
+                _ => {
+                    warn!("Received unauthorized admin request: {:?}", request);
+                    Failure((Status::Unauthorized, "missing or wrong admin token"))
+                }
+            }
+        }
+    }
+
+    #[post("/admin/reload-config")]
+    pub fn reload_config(_admin: Admin) -> Result<String, status::Custom<String>> {
+        match teams::reload_setup() {
+            Ok(()) => Ok(format!("Reloaded {}\n", CONFIG.mscbot_config_path)),
+            Err(why) => {
+                error!("Unable to reload {}, keeping the previous configuration: {:?}",
+                       CONFIG.mscbot_config_path, why);
+                Err(status::Custom(Status::UnprocessableEntity,
+                                   format!("Unable to reload {}, the previous configuration \
+                                            is still in effect: {:?}\n",
+                                           CONFIG.mscbot_config_path, why)))
+            }
+        }
+    }
 
     #[get("/all")]
     pub fn all_fcps() -> DashResult<Json<Vec<nag::FcpWithInfo>>> { Ok(Json(nag::all_fcps()?)) }
@@ -113,10 +191,33 @@ mod api {
         Ok(Json(nag::individual_nags(&username)?))
     }
 
+    #[get("/<username>/feedback")]
+    pub fn member_feedback(
+        username: String,
+    ) -> DashResult<Json<Vec<nag::IndividualFeedbackRequest>>> {
+        Ok(Json(nag::individual_feedback_requests(&username)?))
+    }
+
+    #[get("/history/<owner>/<repo>/<number>")]
+    pub fn proposal_history(
+        owner: String,
+        repo: String,
+        number: i32,
+    ) -> DashResult<Json<nag::IssueHistory>> {
+        Ok(Json(nag::proposal_history(&format!("{}/{}", owner, repo), number)?))
+    }
+
     #[post("/github-webhook", data = "<event>")]
     pub fn github_webhook(event: Event) -> DashResult<()> {
         let conn = &*DB_POOL.get()?;
 
+        if let Some(repo) = event.payload.repository() {
+            if !SETUP.get().tracks_repo(repo) {
+                info!("Ignoring webhook for {}, which isn't tracked", repo);
+                return Ok(());
+            }
+        }
+
         match event.payload {
             Payload::Issues(issue_event) => {
                 handle_issue(conn, issue_event.issue, &issue_event.repository.full_name)?;
@@ -130,8 +231,13 @@ mod api {
                 // possible race conditions if we get a comment hook before the issue one (or we
                 // missed the issue one), so make sure the issue exists first
 
-                if comment_event.action != "deleted" {
-                    // TODO handle deleted comments properly
+                if comment_event.action == "deleted" {
+                    handle_comment_deleted(
+                        conn,
+                        comment_event.comment,
+                        &comment_event.repository.full_name,
+                    )?;
+                } else {
                     handle_issue(
                         conn,
                         comment_event.issue,
@@ -163,10 +269,15 @@ lazy_static! {
         let user_fcps_fragment = include_str!("templates/fcp-user.hbs");
         let user_fcps_template = root_template.replace("{{content}}", user_fcps_fragment);
 
+        let history_fragment = include_str!("templates/fcp-history.hbs");
+        let history_template = root_template.replace("{{content}}", history_fragment);
+
         hbars.register_template_string("all", &all_fcps_template)
             .expect("unable to register all-fcps template");
         hbars.register_template_string("user", &user_fcps_template)
             .expect("unable to register user fcps template");
+        hbars.register_template_string("history", &history_template)
+            .expect("unable to register proposal history template");
 
         hbars
     };
@@ -1,24 +1,89 @@
-// TODO maybe pull from https://github.com/rust-lang/rust-www/blob/master/_data/team.yml instead
-
 use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Read;
+use std::sync::{Arc, PoisonError, RwLock};
 
+use chrono::Duration;
+use diesel::pg::PgConnection;
 use diesel::prelude::*;
+use handlebars::{self, Handlebars};
 use toml;
 
 use super::DB_POOL;
+use config::CONFIG;
 use domain::github::GitHubUser;
 use error::*;
+use github::{self, templates, Label};
 
 //==============================================================================
 // Public API
 //==============================================================================
 
 lazy_static! {
-    pub static ref SETUP: MscbotConfig = read_mscbot_cfg_validated();
+    pub static ref SETUP: Setup = Setup::load();
+}
+
+/// The `mscbot.toml` configuration in effect, which can be replaced while we're running.
+pub struct Setup {
+    current: RwLock<Arc<MscbotConfig>>,
+}
+
+impl Setup {
+    fn load() -> Setup {
+        let path = &CONFIG.mscbot_config_path;
+        let cfg = ok_or!(read_mscbot_cfg_validated(path), why => {
+            error!("Unable to load {}: {:?}", path, why);
+            panic!("Unable to load {}: {:?}", path, why);
+        });
+
+        Setup { current: RwLock::new(Arc::new(cfg)) }
+    }
+
+    /// The configuration in effect. Keep hold of it for as long as the same configuration
+    /// should be used, e.g. while processing a comment.
+    pub fn get(&self) -> Arc<MscbotConfig> {
+        self.current.read().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    /// Read and validate the configuration file again, and put it into effect if it's valid.
+    /// Otherwise the previous configuration stays in effect.
+    fn reload(&self) -> DashResult<()> {
+        let path = &CONFIG.mscbot_config_path;
+        let cfg = read_mscbot_cfg_validated(path)?;
+
+        *self.current.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(cfg);
+        info!("Reloaded {}", path);
+
+        Ok(())
+    }
+}
+
+/// Reload `mscbot.toml`, and bring pending proposals in line with any team changes.
+pub fn reload_setup() -> DashResult<()> {
+    SETUP.reload()?;
+
+    // teams may take their members from other GitHub teams now
+    ok_or!(github::refresh_team_members(), why =>
+        error!("Unable to refresh GitHub team members: {:?}", why));
+
+    // people may have joined or left teams
+    ok_or!(github::reconcile_review_requests(), why =>
+        error!("Unable to reconcile review requests with team membership: {:?}", why));
+
+    Ok(())
 }
 
 #[derive(Debug, Deserialize)]
 pub struct MscbotConfig {
+    /// The mentions the bot answers to in comments, e.g. `@mscbot`.
+    #[serde(default = "default_mentions")]
+    mentions: Vec<String>,
+    /// The organisations whose repos we scrape and accept webhooks from.
+    #[serde(default)]
+    orgs: BTreeMap<String, OrgRepos>,
+    /// Label names replacing the default ones in every repo, keyed by the default name.
+    #[serde(default)]
+    labels: BTreeMap<String, String>,
     fcp_behaviors: BTreeMap<String, FcpBehavior>,
     teams: BTreeMap<TeamLabel, Team>,
 }
@@ -34,6 +99,82 @@ impl MscbotConfig {
         self.teams.iter()
     }
 
+    /// Retrieve an iterator over the GitHub teams whose members we fetch.
+    pub fn github_teams(&self) -> impl Iterator<Item = &str> {
+        self.teams.values().filter_map(|team| team.github_team())
+    }
+
+    /// Check that every `ping` is a GitHub team, and that teams fetching their members from
+    /// GitHub say which GitHub team to use.
+    fn validate_github_teams(&self) -> DashResult<()> {
+        for (label, team) in &self.teams {
+            let well_formed = team.ping.as_ref().map(|ping| {
+                let parts = ping.split('/').collect::<Vec<_>>();
+                parts.len() == 2 && parts.iter().all(|part| !part.is_empty())
+            });
+
+            match well_formed {
+                Some(false) => {
+                    throw!(DashError::Misc(Some(format!("the `ping` of team {} should be a \
+                                                         GitHub team like `org/team`",
+                                                        label.0))));
+                }
+                None if team.fetch_members => {
+                    throw!(DashError::Misc(Some(format!("team {} fetches its members from \
+                                                         GitHub, so it needs a `ping` like \
+                                                         `org/team`",
+                                                        label.0))));
+                }
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The mentions the bot answers to in comments.
+    pub fn mentions(&self) -> &[String] {
+        &self.mentions
+    }
+
+    /// Retrieve an iterator over the organisations we work in.
+    pub fn orgs(&self) -> impl Iterator<Item = &str> {
+        self.orgs.keys().map(|org| org.as_str())
+    }
+
+    /// Do we work in this repo (given as `org/repo`)?
+    pub fn tracks_repo(&self, repo: &str) -> bool {
+        let mut parts = repo.splitn(2, '/');
+        match (parts.next(), parts.next()) {
+            (Some(org), Some(name)) => {
+                self.orgs.get(org).map(|repos| repos.tracks(name)).unwrap_or(false)
+            }
+            _ => false,
+        }
+    }
+
+    /// Check that the bot can be mentioned and knows where to look.
+    fn validate_mentions_and_orgs(&self) -> DashResult<()> {
+        if self.mentions.is_empty() {
+            throw!(DashError::Misc(Some("no mentions for the bot".to_string())));
+        }
+
+        for mention in &self.mentions {
+            if !mention.starts_with('@') || mention.len() < 2 ||
+               mention.contains(char::is_whitespace) {
+                throw!(DashError::Misc(Some(format!("`{}` isn't a mention, expected e.g. \
+                                                     `@mscbot`",
+                                                    mention))));
+            }
+        }
+
+        if self.orgs.is_empty() {
+            warn!("no organisations in mscbot.toml, so no repos will be scraped");
+        }
+
+        Ok(())
+    }
+
     /// Are we allowed to auto-close issues after F-FCP in this repo?
     pub fn should_ffcp_auto_close(&self, repo: &str) -> bool {
         self.fcp_behaviors.get(repo).map(|fcp| fcp.close).unwrap_or_default()
@@ -43,6 +184,243 @@ impl MscbotConfig {
     pub fn should_ffcp_auto_postpone(&self, repo: &str) -> bool {
         self.fcp_behaviors.get(repo).map(|fcp| fcp.postpone).unwrap_or_default()
     }
+
+    /// Are we allowed to auto-merge pull requests after F-FCP in this repo?
+    pub fn should_ffcp_auto_merge(&self, repo: &str) -> bool {
+        self.fcp_behaviors.get(repo).map(|fcp| fcp.merge).unwrap_or_default()
+    }
+
+    /// How should pull requests be merged after F-FCP in this repo?
+    pub fn merge_method(&self, repo: &str) -> MergeMethod {
+        self.fcp_behaviors
+            .get(repo)
+            .and_then(|fcp| fcp.merge_method)
+            .unwrap_or(MergeMethod::Merge)
+    }
+
+    /// Which issue should be opened after a merge FCP finishes in this repo, if any?
+    pub fn follow_up_issue(&self, repo: &str) -> Option<&FollowUpIssue> {
+        self.fcp_behaviors.get(repo).and_then(|fcp| fcp.follow_up.as_ref())
+    }
+
+    /// What a label is called in this repo. `None` if the repo doesn't use the label, which is
+    /// written as an empty name in `mscbot.toml`.
+    pub fn label_name(&self, repo: &str, label: Label) -> Option<String> {
+        let default = label.as_str();
+
+        let name = self.fcp_behaviors
+            .get(repo)
+            .and_then(|fcp| fcp.labels.get(default))
+            .or_else(|| self.labels.get(default))
+            .map(|name| name.as_str())
+            .unwrap_or(default);
+
+        if name.is_empty() {
+            None
+        } else {
+            Some(name.to_string())
+        }
+    }
+
+    /// Check that every label mapping refers to a label we know.
+    fn validate_labels(&self) -> DashResult<()> {
+        let repo_labels = self.fcp_behaviors.values().flat_map(|fcp| fcp.labels.keys());
+
+        for default in self.labels.keys().chain(repo_labels) {
+            if !Label::ALL.iter().any(|label| label.as_str() == default) {
+                let known = Label::ALL.iter().map(|label| label.as_str()).collect::<Vec<_>>();
+                throw!(DashError::Misc(Some(format!("unknown label `{}`, expected one of {}",
+                                                    default,
+                                                    known.join(", ")))));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// This repo's own version of a comment template, if it has one.
+    pub fn comment_template(&self, repo: &str, name: &str) -> Option<&str> {
+        self.fcp_behaviors
+            .get(repo)
+            .and_then(|fcp| fcp.comments.get(name))
+            .map(|template| template.as_str())
+    }
+
+    /// Check every comment template that a repo overrides.
+    fn validate_comment_templates(&self) -> DashResult<()> {
+        for (repo, fcp) in &self.fcp_behaviors {
+            for (name, template) in &fcp.comments {
+                ok_or!(templates::validate(name, template), why => {
+                    error!("bad comment template for {}: {:?}", repo, why);
+                    throw!(why);
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// How long does the final comment period last for an issue in this repo with these labels?
+    ///
+    /// Team overrides take precedence over the repo setting. If several tagged teams
+    /// override the duration, the longest one wins.
+    pub fn fcp_duration(&self, repo: &str, labels: &[String]) -> Duration {
+        let team_days = self.teams()
+            .filter(|&(label, _)| labels.contains(&label.0))
+            .filter_map(|(_, team)| team.fcp_duration_days)
+            .max();
+
+        let days = team_days
+            .or_else(|| self.fcp_behaviors.get(repo).and_then(|fcp| fcp.fcp_duration_days))
+            .unwrap_or(DEFAULT_FCP_DURATION_DAYS);
+
+        Duration::days(days)
+    }
+
+    /// Check that every final comment period lasts at least a day.
+    fn validate_fcp_durations(&self) -> DashResult<()> {
+        let repo_days = self.fcp_behaviors
+            .iter()
+            .filter_map(|(repo, fcp)| fcp.fcp_duration_days.map(|days| (repo, days)));
+        let team_days = self.teams
+            .iter()
+            .filter_map(|(label, team)| team.fcp_duration_days.map(|days| (&label.0, days)));
+
+        for (owner, days) in repo_days.chain(team_days) {
+            if days < 1 {
+                throw!(DashError::Misc(Some(format!("the `fcp_duration_days` of {} should be \
+                                                     at least 1, not {}",
+                                                    owner, days))));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// What happens to the reviews of a proposal in this repo when its disposition changes?
+    pub fn disposition_change_reviews(&self, repo: &str) -> ReviewReset {
+        self.fcp_behaviors
+            .get(repo)
+            .and_then(|fcp| fcp.disposition_change_reviews)
+            .unwrap_or(ReviewReset::Reset)
+    }
+
+    /// Which quorum policies have to be met before FCP can start on an issue in this repo
+    /// with these labels?
+    ///
+    /// Team policies take precedence over the repo policy. If several tagged teams declare
+    /// a policy, all of them have to be met.
+    pub fn quorum_policies(&self, repo: &str, labels: &[String]) -> Vec<QuorumPolicy> {
+        let mut policies = Vec::new();
+        for (_, team) in self.teams().filter(|&(label, _)| labels.contains(&label.0)) {
+            if let Some(policy) = team.quorum {
+                if !policies.contains(&policy) {
+                    policies.push(policy);
+                }
+            }
+        }
+
+        if policies.is_empty() {
+            let repo_policy = self.fcp_behaviors.get(repo).and_then(|fcp| fcp.quorum);
+            policies.push(repo_policy.unwrap_or(QuorumPolicy::Rfcbot));
+        }
+
+        policies
+    }
+
+    /// Check that every quorum policy can be met.
+    fn validate_quorum_policies(&self) -> DashResult<()> {
+        let repo_policies = self.fcp_behaviors
+            .iter()
+            .filter_map(|(repo, fcp)| fcp.quorum.map(|policy| (repo, policy)));
+        let team_policies = self.teams
+            .iter()
+            .filter_map(|(label, team)| team.quorum.map(|policy| (&label.0, policy)));
+
+        for (owner, policy) in repo_policies.chain(team_policies) {
+            let problem = match policy {
+                QuorumPolicy::NOfM { n: 0 } => Some("`n` should be at least 1"),
+                QuorumPolicy::Percentage { percent } if percent == 0 || percent > 100 => {
+                    Some("`percent` should be between 1 and 100")
+                }
+                _ => None,
+            };
+
+            if let Some(problem) = problem {
+                throw!(DashError::Misc(Some(format!("the `quorum` of {} is invalid: {}",
+                                                    owner, problem))));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check that every team's `min_reviewers` can be met. The size of teams which fetch their
+    /// members from GitHub is only known once the scraper has found them, so it isn't checked.
+    fn validate_team_rules(&self) -> DashResult<()> {
+        for (label, team) in &self.teams {
+            let min = match team.min_reviewers {
+                Some(min) => min,
+                None => continue,
+            };
+
+            // leads and required reviewers count as members
+            let members = team.member_logins().count();
+
+            if min == 0 {
+                throw!(DashError::Misc(Some(format!("the `min_reviewers` of team {} should be \
+                                                     at least 1",
+                                                    label.0))));
+            }
+            if !team.fetch_members && min > members {
+                throw!(DashError::Misc(Some(format!("team {} has only {} members, so its \
+                                                     `min_reviewers` of {} can never be met",
+                                                    label.0, members, min))));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Does every tagged team have to reach its quorum on its own in this repo?
+    pub fn per_team_quorum(&self, repo: &str) -> bool {
+        self.fcp_behaviors.get(repo).map(|fcp| fcp.per_team_quorum).unwrap_or_default()
+    }
+
+    /// Which quorum policy does a team have to meet on its own in this repo?
+    ///
+    /// The team's policy takes precedence over the repo policy.
+    pub fn team_quorum_policy(&self, repo: &str, team: &Team) -> QuorumPolicy {
+        team.quorum
+            .or_else(|| self.fcp_behaviors.get(repo).and_then(|fcp| fcp.quorum))
+            .unwrap_or(QuorumPolicy::Rfcbot)
+    }
+}
+
+/// The length of a final comment period unless `mscbot.toml` says otherwise.
+pub const DEFAULT_FCP_DURATION_DAYS: i64 = 10;
+
+fn default_mentions() -> Vec<String> {
+    vec!["@mscbot".to_string()]
+}
+
+/// Which repos of an organisation we work in, by name without the organisation.
+///
+/// All of them by default; `include` narrows that down to the listed ones, and `exclude`
+/// leaves the listed ones out.
+#[derive(Debug, Default, Deserialize)]
+pub struct OrgRepos {
+    #[serde(default)]
+    include: Vec<String>,
+    #[serde(default)]
+    exclude: Vec<String>,
+}
+
+impl OrgRepos {
+    fn tracks(&self, name: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|r| r == name)) &&
+        !self.exclude.iter().any(|r| r == name)
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -51,23 +429,220 @@ pub struct FcpBehavior {
     close: bool,
     #[serde(default)]
     postpone: bool,
+    #[serde(default)]
+    merge: bool,
+    merge_method: Option<MergeMethod>,
+    follow_up: Option<FollowUpIssue>,
+    /// Comment templates replacing the default ones, by name.
+    #[serde(default)]
+    comments: BTreeMap<String, String>,
+    /// Label names replacing the default ones in this repo, keyed by the default name.
+    #[serde(default)]
+    labels: BTreeMap<String, String>,
+    fcp_duration_days: Option<i64>,
+    quorum: Option<QuorumPolicy>,
+    disposition_change_reviews: Option<ReviewReset>,
+    /// Whether every tagged team has to reach its quorum on its own, instead of all of their
+    /// members counting towards one quorum.
+    #[serde(default)]
+    per_team_quorum: bool,
 }
 
+/// An issue to open once a merge FCP has finished, e.g. to track writing the spec.
+///
+/// `title` and `body` are handlebars templates which can use `{{issue.number}}`,
+/// `{{issue.title}}`, `{{issue.repository}}`, `{{issue.url}}` and `{{initiator}}`.
 #[derive(Debug, Deserialize)]
-pub struct Team {
-    // FIXME(2018-05-16):
-    // The two following first fields are not used anymore.
-    // But they could still be useful. Consider what usage they could have.
+pub struct FollowUpIssue {
+    /// The repository to open the issue in, the one of the proposal if not set.
+    repository: Option<String>,
+    title: String,
+    body: String,
+}
+
+impl FollowUpIssue {
+    /// The repository to open the follow-up of a proposal in `proposal_repo` in.
+    pub fn repository<'a>(&'a self, proposal_repo: &'a str) -> &'a str {
+        self.repository.as_ref().map(|r| r.as_str()).unwrap_or(proposal_repo)
+    }
+
+    /// Render the title and body of the follow-up for an issue and the proposal's initiator.
+    pub fn render(&self,
+                  repo: &str,
+                  number: i32,
+                  title: &str,
+                  initiator: &str)
+                  -> DashResult<(String, String)> {
+        let mut hbars = Handlebars::new();
+        // this is markdown, not HTML
+        hbars.register_escape_fn(handlebars::no_escape);
+
+        let data = json!({
+            "issue": {
+                "number": number,
+                "title": title,
+                "repository": repo,
+                "url": format!("https://github.com/{}/issues/{}", repo, number),
+            },
+            "initiator": initiator,
+        });
+
+        Ok((hbars.template_render(&self.title, &data)?,
+            hbars.template_render(&self.body, &data)?))
+    }
+}
+
+/// How GitHub should merge a pull request.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum MergeMethod {
+    /// A merge commit.
+    Merge,
+    /// All commits squashed into one.
+    Squash,
+    /// The commits rebased onto the base branch.
+    Rebase,
+}
+
+impl MergeMethod {
+    /// The name of the method in GitHub's API.
+    pub fn repr(self) -> &'static str {
+        match self {
+            MergeMethod::Merge => "merge",
+            MergeMethod::Squash => "squash",
+            MergeMethod::Rebase => "rebase",
+        }
+    }
+}
+
+/// What happens to the reviews of a proposal when its disposition is changed.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReviewReset {
+    /// Every review stays as it is.
+    Keep,
+    /// Reviews start over, as if the proposal had just been made by whoever changed it.
+    Reset,
+}
+
+/// A rule deciding when enough reviewers have signed off for FCP to start.
+///
+/// In `mscbot.toml` this is written as e.g. `quorum = { policy = "n-of-m", n = 3 }`.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+#[serde(tag = "policy", rename_all = "kebab-case")]
+pub enum QuorumPolicy {
+    /// A majority of reviewers, with at most two still outstanding. This is what rfcbot does.
+    Rfcbot,
+    /// More than half of the reviewers.
+    Majority,
+    /// Every single reviewer.
+    Unanimous,
+    /// At least `n` reviewers, or all of them if there are fewer than `n`.
+    NOfM { n: usize },
+    /// At least `percent` percent of the reviewers.
+    Percentage { percent: usize },
+    /// Every reviewer except for at most `k` of them.
+    AllButK { k: usize },
+}
+
+impl QuorumPolicy {
+    /// Is the policy satisfied by these review counts?
+    ///
+    /// No policy is satisfied before anybody has reviewed.
+    pub fn is_met(self, num_complete: usize, num_outstanding: usize) -> bool {
+        use self::QuorumPolicy::*;
+        let total = num_complete + num_outstanding;
+
+        num_complete > 0 && match self {
+            Rfcbot => num_outstanding < num_complete && num_outstanding < 3,
+            Majority => num_complete * 2 > total,
+            Unanimous => num_outstanding == 0,
+            NOfM { n } => num_complete >= n.min(total),
+            Percentage { percent } => num_complete * 100 >= percent * total,
+            AllButK { k } => num_outstanding <= k,
+        }
+    }
 
-    //name: String,
-    //ping: String,
+    /// Describes the policy for the tracking comment, e.g. "all reviewers approve".
+    pub fn description(self) -> String {
+        use self::QuorumPolicy::*;
+        match self {
+            Rfcbot => "a majority of reviewers approve".to_string(),
+            Majority => "more than half of the reviewers approve".to_string(),
+            Unanimous => "all reviewers approve".to_string(),
+            NOfM { n } => format!("at least {} reviewers approve", n),
+            Percentage { percent } => format!("at least {}% of reviewers approve", percent),
+            AllButK { k } => format!("all but {} of the reviewers approve", k),
+        }
+    }
+}
 
+#[derive(Debug, Deserialize)]
+pub struct Team {
+    /// The name to show for the team, e.g. "Core team".
+    name: Option<String>,
+    /// The GitHub team (`org/team-slug`) this team corresponds to, which is mentioned in the
+    /// tracking comment so that its subscribers are notified.
+    ping: Option<String>,
+    /// Whether the members of the `ping` team count as members of this team, in addition to
+    /// `members`. They're fetched from GitHub by the scraper.
+    #[serde(default)]
+    fetch_members: bool,
+    #[serde(default)]
     members: Vec<String>,
+    /// Members of whom at least one has to review a proposal before FCP can start.
+    #[serde(default)]
+    leads: Vec<String>,
+    /// Members who all have to review a proposal before FCP can start.
+    #[serde(default)]
+    required_reviewers: Vec<String>,
+    /// How many members have to review a proposal before FCP can start, on top of the quorum.
+    min_reviewers: Option<usize>,
+    fcp_duration_days: Option<i64>,
+    quorum: Option<QuorumPolicy>,
 }
 
 impl Team {
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_ref().map(|name| name.as_str())
+    }
+
+    pub fn ping(&self) -> Option<&str> {
+        self.ping.as_ref().map(|ping| ping.as_str())
+    }
+
+    /// The members listed in `mscbot.toml`, not including those fetched from GitHub. Leads and
+    /// required reviewers are members too, whether they're listed in `members` or not.
     pub fn member_logins(&self) -> impl Iterator<Item = &str> {
-        self.members.iter().map(|s| s.as_str())
+        let mut logins = Vec::new();
+        for login in self.members.iter().chain(&self.leads).chain(&self.required_reviewers) {
+            if !logins.contains(&login.as_str()) {
+                logins.push(login.as_str());
+            }
+        }
+
+        logins.into_iter()
+    }
+
+    pub fn leads(&self) -> &[String] {
+        &self.leads
+    }
+
+    pub fn required_reviewers(&self) -> &[String] {
+        &self.required_reviewers
+    }
+
+    pub fn min_reviewers(&self) -> Option<usize> {
+        self.min_reviewers
+    }
+
+    /// The GitHub team to fetch further members from, if any.
+    pub fn github_team(&self) -> Option<&str> {
+        if self.fetch_members {
+            self.ping.as_ref().map(|ping| ping.as_str())
+        } else {
+            None
+        }
     }
 }
 
@@ -79,45 +654,115 @@ pub struct TeamLabel(pub String);
 // Implementation details
 //==============================================================================
 
-/// Read the validated `mscbot.toml` configuration file.
-fn read_mscbot_cfg_validated() -> MscbotConfig {
-    let cfg = read_mscbot_cfg();
+/// Read and validate the `mscbot.toml` configuration file at `path`, and remember the team
+/// members we haven't seen before.
+pub fn read_mscbot_cfg_validated(path: &str) -> DashResult<MscbotConfig> {
+    let (cfg, new_users) = check_mscbot_cfg_members(path)?;
 
-    cfg.teams.values().for_each(|team|
-        team.validate()
-            .expect("unable to verify team member from database.
-if you're running this for tests, make sure you've pulled github users from prod")
-    );
+    // only remember the new members once we know the whole file is valid
+    let conn = &*DB_POOL.get()?;
+    for user in &new_users {
+        info!("adding team member {}", user.login);
+        github::handle_user(conn, user)?;
+    }
 
-    cfg
+    Ok(cfg)
 }
 
-/// Read the unprocessed `mscbot.toml` configuration file.
-fn read_mscbot_cfg() -> MscbotConfig {
-    read_mscbot_cfg_from(
-        include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/mscbot.toml")))
+/// Read and validate the `mscbot.toml` configuration file at `path`, without writing
+/// anything to the database.
+pub fn check_mscbot_cfg(path: &str) -> DashResult<MscbotConfig> {
+    check_mscbot_cfg_members(path).map(|(cfg, _)| cfg)
 }
 
-fn read_mscbot_cfg_from(input: &str) -> MscbotConfig {
-    toml::from_str(input).expect("couldn't parse mscbot.toml!")
+/// Read and validate the `mscbot.toml` configuration file at `path`. Returns it along with
+/// the team members which we don't know yet.
+fn check_mscbot_cfg_members(path: &str) -> DashResult<(MscbotConfig, Vec<GitHubUser>)> {
+    let cfg = read_mscbot_cfg(path)?;
+
+    cfg.validate_mentions_and_orgs()?;
+    cfg.validate_comment_templates()?;
+    cfg.validate_labels()?;
+    cfg.validate_github_teams()?;
+    cfg.validate_fcp_durations()?;
+    cfg.validate_quorum_policies()?;
+    cfg.validate_team_rules()?;
+
+    let conn = &*DB_POOL.get()?;
+
+    let mut new_users = Vec::new();
+    let mut bad_logins = Vec::new();
+    for (label, team) in &cfg.teams {
+        let (found, bad_members) = team.resolve_members(conn);
+        new_users.extend(found);
+        bad_logins.extend(bad_members.into_iter().map(|bad| format!("{} in {}", bad, label.0)));
+    }
+
+    if !bad_logins.is_empty() {
+        error!("team members who couldn't be found on GitHub: {:?}", bad_logins);
+        let reason = format!("these team members couldn't be found on GitHub: {}",
+                             bad_logins.join(", "));
+        throw!(DashError::Misc(Some(reason)));
+    }
+
+    Ok((cfg, new_users))
+}
+
+/// Read the unprocessed `mscbot.toml` configuration file at `path`.
+fn read_mscbot_cfg(path: &str) -> DashResult<MscbotConfig> {
+    let mut input = String::new();
+    File::open(path)?.read_to_string(&mut input)?;
+
+    read_mscbot_cfg_from(&input)
+}
+
+fn read_mscbot_cfg_from(input: &str) -> DashResult<MscbotConfig> {
+    toml::from_str(input).map_err(|why| {
+        DashError::Misc(Some(format!("couldn't parse mscbot.toml: {}", why)))
+    })
 }
 
 impl Team {
-    fn validate(&self) -> DashResult<()> {
+    /// Look up the listed members we haven't seen yet on GitHub. Returns the GitHub users which
+    /// we don't know yet, and the logins which couldn't be resolved to a GitHub user (with the
+    /// reason, if it isn't that there's no such user). Nothing is written to the database.
+    fn resolve_members(&self, conn: &PgConnection) -> (Vec<GitHubUser>, Vec<String>) {
         use domain::schema::githubuser::dsl::*;
-        let conn = &*(DB_POOL.get()?);
 
-        // bail if they don't exist, but we don't want to actually keep the id in ram
+        let mut new_users = Vec::new();
+        let mut bad_logins = Vec::new();
         for member_login in self.member_logins() {
-            let check_login = githubuser.filter(login.eq(member_login))
-                                        .first::<GitHubUser>(conn);
-            ok_or!(check_login, why => {
-                error!("unable to find {} in database: {:?}", member_login, why);
-                throw!(why);
-            });
+            // we don't want to actually keep the id in ram
+            let known = githubuser.filter(login.eq(member_login))
+                                  .first::<GitHubUser>(conn)
+                                  .optional();
+            match known {
+                Ok(Some(_)) => continue,
+                Ok(None) => (),
+                Err(why) => {
+                    bad_logins.push(format!("{} (couldn't look it up: {:?})", member_login, why));
+                    continue;
+                }
+            }
+
+            match github::GH.user(member_login) {
+                // logins aren't case sensitive on GitHub, but they are for us
+                Ok(Some(ref user)) if user.login != member_login => {
+                    bad_logins.push(format!("{} (spelled {} on GitHub)", member_login, user.login));
+                }
+                Ok(Some(user)) => {
+                    info!("found team member {} on GitHub", user.login);
+                    new_users.push(user);
+                }
+                Ok(None) => bad_logins.push(member_login.to_string()),
+                Err(why) => {
+                    bad_logins.push(format!("{} (couldn't look it up on GitHub: {:?})",
+                                            member_login, why));
+                }
+            }
         }
 
-        Ok(())
+        (new_users, bad_logins)
     }
 }
 
@@ -132,25 +777,60 @@ mod test {
     #[test]
     fn setup_parser_correct() {
 let test = r#"
+mentions = ["@mscbot", "@spec-bot"]
+
+[orgs.rust-lang]
+exclude = ["rust-www"]
+
+[orgs.wibble]
+include = ["epsilon", "spec"]
+
+[orgs.bazquux]
+
+[labels]
+closed = ""
+finished-final-comment-period = "fcp-finished"
+
 [fcp_behaviors]
 
 [fcp_behaviors."rust-lang/alpha"]
 close = true
 postpone = true
+disposition_change_reviews = "keep"
 
 [fcp_behaviors."foobar/beta"]
 close = false
+per_team_quorum = true
+merge = true
+merge_method = "squash"
+quorum = { policy = "unanimous" }
 
 [fcp_behaviors."bazquux/gamma"]
 postpone = false
+fcp_duration_days = 7
+
+[fcp_behaviors."bazquux/gamma".comments]
+fcp-cancelled = "@{{author}} withdrew this proposal."
 
 [fcp_behaviors."wibble/epsilon"]
 
+[fcp_behaviors."wibble/epsilon".labels]
+closed = "rejected"
+postponed = ""
+
+[fcp_behaviors."wibble/epsilon".follow_up]
+repository = "wibble/spec"
+title = "Write the spec for {{issue.title}} ({{issue.repository}}#{{issue.number}})"
+body = "{{issue.url}} was accepted, @{{initiator}} please write it up & link it here."
+
 [teams]
 
 [teams.avengers]
 name = "The Avengers"
 ping = "marvel/avengers"
+leads = ["captainamerica"]
+required_reviewers = ["thor", "nickfury"]
+min_reviewers = 3
 members = [
   "hulk",
   "thor",
@@ -163,6 +843,9 @@ members = [
 [teams.justice-league]
 name = "Justice League of America"
 ping = "dc-comics/justice-league"
+fetch_members = true
+fcp_duration_days = 14
+quorum = { policy = "n-of-m", n = 3 }
 members = [
   "superman",
   "wonderwoman",
@@ -171,7 +854,7 @@ members = [
   "theflash"
 ]
 "#;
-        let cfg = read_mscbot_cfg_from(test);
+        let cfg = read_mscbot_cfg_from(test).unwrap();
 
         // Labels are correct:
         assert_eq!(cfg.team_labels().map(|tl| tl.0.clone()).collect::<Vec<_>>(),
@@ -182,17 +865,30 @@ members = [
             cfg.teams().map(|(k, v)| (k.0.clone(), v.clone())).collect();
 
         let avengers = map.get("avengers").unwrap();
-        //assert_eq!(avengers.name, "The Avengers");
-        //assert_eq!(avengers.ping, "marvel/avengers");
+        assert_eq!(avengers.name(), Some("The Avengers"));
+        assert_eq!(avengers.ping(), Some("marvel/avengers"));
         assert_eq!(avengers.member_logins().collect::<Vec<_>>(),
             vec!["hulk", "thor", "thevision", "blackwidow",
-                 "spiderman", "captainamerica"]);
+                 "spiderman", "captainamerica", "nickfury"]);
+        assert_eq!(avengers.leads(), &["captainamerica".to_string()][..]);
+        assert_eq!(avengers.required_reviewers(),
+                   &["thor".to_string(), "nickfury".to_string()][..]);
+        assert_eq!(avengers.min_reviewers(), Some(3));
 
         let jsa = map.get("justice-league").unwrap();
-        //assert_eq!(jsa.name, "Justice League of America");
-        //assert_eq!(jsa.ping, "dc-comics/justice-league");
+        assert_eq!(jsa.name(), Some("Justice League of America"));
+        assert_eq!(jsa.ping(), Some("dc-comics/justice-league"));
         assert_eq!(jsa.member_logins().collect::<Vec<_>>(),
             vec!["superman", "wonderwoman", "aquaman", "batman", "theflash"]);
+        assert!(jsa.leads().is_empty());
+        assert!(jsa.required_reviewers().is_empty());
+        assert_eq!(jsa.min_reviewers(), None);
+
+        // GitHub teams correct:
+        assert!(cfg.validate_github_teams().is_ok());
+        assert_eq!(avengers.github_team(), None);
+        assert_eq!(jsa.github_team(), Some("dc-comics/justice-league"));
+        assert_eq!(cfg.github_teams().collect::<Vec<_>>(), vec!["dc-comics/justice-league"]);
 
         // Random non-existent team does not exist:
         assert!(map.get("random").is_none());
@@ -208,18 +904,290 @@ members = [
         assert!(!cfg.should_ffcp_auto_postpone("wibble/epsilon"));
         assert!(!cfg.should_ffcp_auto_close("random"));
         assert!(!cfg.should_ffcp_auto_postpone("random"));
+        assert!(cfg.should_ffcp_auto_merge("foobar/beta"));
+        assert!(!cfg.should_ffcp_auto_merge("rust-lang/alpha"));
+        assert!(!cfg.should_ffcp_auto_merge("random"));
+        assert_eq!(cfg.merge_method("foobar/beta"), MergeMethod::Squash);
+        assert_eq!(cfg.merge_method("rust-lang/alpha"), MergeMethod::Merge);
+
+        // Comment templates correct:
+        assert!(cfg.validate_comment_templates().is_ok());
+        assert_eq!(cfg.comment_template("bazquux/gamma", "fcp-cancelled"),
+                   Some("@{{author}} withdrew this proposal."));
+        assert_eq!(cfg.comment_template("bazquux/gamma", "fcp-finished"), None);
+        assert_eq!(cfg.comment_template("rust-lang/alpha", "fcp-cancelled"), None);
+
+        // Mentions and orgs correct:
+        assert!(cfg.validate_mentions_and_orgs().is_ok());
+        assert_eq!(cfg.mentions(), &["@mscbot".to_string(), "@spec-bot".to_string()][..]);
+        assert_eq!(cfg.orgs().collect::<Vec<_>>(), vec!["bazquux", "rust-lang", "wibble"]);
+        assert!(cfg.tracks_repo("rust-lang/alpha"));
+        assert!(!cfg.tracks_repo("rust-lang/rust-www"));
+        assert!(cfg.tracks_repo("wibble/epsilon"));
+        assert!(!cfg.tracks_repo("wibble/zeta"));
+        assert!(cfg.tracks_repo("bazquux/gamma"));
+        assert!(!cfg.tracks_repo("foobar/beta"));
+        assert!(!cfg.tracks_repo("rust-lang"));
+
+        // Per-team quorum correct:
+        assert!(cfg.per_team_quorum("foobar/beta"));
+        assert!(!cfg.per_team_quorum("rust-lang/alpha"));
+        assert!(!cfg.per_team_quorum("random"));
+        assert_eq!(cfg.team_quorum_policy("foobar/beta", avengers), QuorumPolicy::Unanimous);
+        assert_eq!(cfg.team_quorum_policy("foobar/beta", jsa), QuorumPolicy::NOfM { n: 3 });
+        assert_eq!(cfg.team_quorum_policy("rust-lang/alpha", avengers), QuorumPolicy::Rfcbot);
+
+        // Labels correct:
+        assert!(cfg.validate_labels().is_ok());
+        assert_eq!(cfg.label_name("rust-lang/alpha", Label::PFCP),
+                   Some("proposed-final-comment-period".to_string()));
+        assert_eq!(cfg.label_name("rust-lang/alpha", Label::FFCP),
+                   Some("fcp-finished".to_string()));
+        assert_eq!(cfg.label_name("rust-lang/alpha", Label::Closed), None);
+        assert_eq!(cfg.label_name("wibble/epsilon", Label::Closed), Some("rejected".to_string()));
+        assert_eq!(cfg.label_name("wibble/epsilon", Label::Postponed), None);
+        assert_eq!(cfg.label_name("wibble/epsilon", Label::FFCP),
+                   Some("fcp-finished".to_string()));
+
+        // Follow-up issues correct:
+        assert!(cfg.follow_up_issue("rust-lang/alpha").is_none());
+        let follow_up = cfg.follow_up_issue("wibble/epsilon").unwrap();
+        assert_eq!(follow_up.repository("wibble/epsilon"), "wibble/spec");
+        assert_eq!(follow_up.render("wibble/epsilon", 1234, "Better <things>", "hulk").unwrap(),
+                   ("Write the spec for Better <things> (wibble/epsilon#1234)".to_string(),
+                    "https://github.com/wibble/epsilon/issues/1234 was accepted, @hulk please \
+                     write it up & link it here.".to_string()));
+
+        // FCP durations correct:
+        let avengers = vec!["avengers".to_string()];
+        let both = vec!["avengers".to_string(), "justice-league".to_string()];
+        assert_eq!(cfg.fcp_duration("rust-lang/alpha", &avengers),
+                   Duration::days(DEFAULT_FCP_DURATION_DAYS));
+        assert_eq!(cfg.fcp_duration("bazquux/gamma", &avengers), Duration::days(7));
+        assert_eq!(cfg.fcp_duration("bazquux/gamma", &both), Duration::days(14));
+        assert_eq!(cfg.fcp_duration("random", &[]), Duration::days(DEFAULT_FCP_DURATION_DAYS));
+
+        // Disposition changes correct:
+        assert_eq!(cfg.disposition_change_reviews("rust-lang/alpha"), ReviewReset::Keep);
+        assert_eq!(cfg.disposition_change_reviews("foobar/beta"), ReviewReset::Reset);
+        assert_eq!(cfg.disposition_change_reviews("random"), ReviewReset::Reset);
+
+        // Quorum policies correct:
+        let jsa = vec!["justice-league".to_string()];
+        assert_eq!(cfg.quorum_policies("rust-lang/alpha", &avengers),
+                   vec![QuorumPolicy::Rfcbot]);
+        assert_eq!(cfg.quorum_policies("foobar/beta", &avengers),
+                   vec![QuorumPolicy::Unanimous]);
+        assert_eq!(cfg.quorum_policies("foobar/beta", &jsa),
+                   vec![QuorumPolicy::NOfM { n: 3 }]);
+        assert_eq!(cfg.quorum_policies("foobar/beta", &both),
+                   vec![QuorumPolicy::NOfM { n: 3 }]);
+    }
+
+    #[test]
+    fn default_mention() {
+        let cfg = read_mscbot_cfg_from(r#"
+[fcp_behaviors]
+
+[teams]
+"#).unwrap();
+        assert_eq!(cfg.mentions(), &["@mscbot".to_string()][..]);
+        assert!(!cfg.tracks_repo("rust-lang/rust"));
+    }
+
+    #[test]
+    fn bad_mentions_rejected() {
+        let cfg = read_mscbot_cfg_from(r#"
+mentions = ["mscbot"]
+
+[fcp_behaviors]
+
+[teams]
+"#).unwrap();
+        assert!(cfg.validate_mentions_and_orgs().is_err());
+
+        let cfg = read_mscbot_cfg_from(r#"
+mentions = []
+
+[fcp_behaviors]
+
+[teams]
+"#).unwrap();
+        assert!(cfg.validate_mentions_and_orgs().is_err());
+    }
+
+    #[test]
+    fn github_team_needs_ping() {
+        let cfg = read_mscbot_cfg_from(r#"
+[fcp_behaviors]
+
+[teams.T-core]
+fetch_members = true
+"#).unwrap();
+        assert!(cfg.validate_github_teams().is_err());
+
+        let cfg = read_mscbot_cfg_from(r#"
+[fcp_behaviors]
+
+[teams.T-core]
+ping = "spec-core"
+fetch_members = true
+"#).unwrap();
+        assert!(cfg.validate_github_teams().is_err());
+    }
+
+    #[test]
+    fn unparseable_rejected() {
+        assert!(read_mscbot_cfg_from("teams = 3").is_err());
+        assert!(read_mscbot_cfg("/nonexistent/mscbot.toml").is_err());
+    }
+
+    #[test]
+    fn unknown_label_rejected() {
+        let cfg = read_mscbot_cfg_from(r#"
+[labels]
+final-comment-period-proposed = "pfcp"
+
+[fcp_behaviors]
+
+[teams]
+"#).unwrap();
+        assert!(cfg.validate_labels().is_err());
+    }
+
+    #[test]
+    fn bad_fcp_durations_rejected() {
+        let cfg = read_mscbot_cfg_from(r#"
+[fcp_behaviors."rust-lang/alpha"]
+fcp_duration_days = 0
+
+[teams]
+"#).unwrap();
+        assert!(cfg.validate_fcp_durations().is_err());
+
+        let cfg = read_mscbot_cfg_from(r#"
+[fcp_behaviors]
+
+[teams.T-core]
+fcp_duration_days = -3
+"#).unwrap();
+        assert!(cfg.validate_fcp_durations().is_err());
+
+        let cfg = read_mscbot_cfg_from(r#"
+[fcp_behaviors."rust-lang/alpha"]
+fcp_duration_days = 1
+
+[teams.T-core]
+fcp_duration_days = 14
+"#).unwrap();
+        assert!(cfg.validate_fcp_durations().is_ok());
+    }
+
+    #[test]
+    fn bad_quorum_policies_rejected() {
+        for policy in &["{ policy = \"n-of-m\", n = 0 }",
+                        "{ policy = \"percentage\", percent = 0 }",
+                        "{ policy = \"percentage\", percent = 101 }"] {
+            let cfg = read_mscbot_cfg_from(&format!(r#"
+[fcp_behaviors."rust-lang/alpha"]
+quorum = {}
+
+[teams]
+"#, policy)).unwrap();
+            assert!(cfg.validate_quorum_policies().is_err(), "{} was accepted", policy);
+
+            let cfg = read_mscbot_cfg_from(&format!(r#"
+[fcp_behaviors]
+
+[teams.T-core]
+quorum = {}
+"#, policy)).unwrap();
+            assert!(cfg.validate_quorum_policies().is_err(), "{} was accepted", policy);
+        }
+
+        let cfg = read_mscbot_cfg_from(r#"
+[fcp_behaviors."rust-lang/alpha"]
+quorum = { policy = "percentage", percent = 100 }
+
+[teams.T-core]
+quorum = { policy = "all-but-k", k = 0 }
+"#).unwrap();
+        assert!(cfg.validate_quorum_policies().is_ok());
+    }
+
+    #[test]
+    fn bad_team_rules_rejected() {
+        for min in &[0, 4] {
+            let cfg = read_mscbot_cfg_from(&format!(r#"
+[fcp_behaviors]
+
+[teams.T-core]
+members = ["alice", "bob"]
+leads = ["carol"]
+min_reviewers = {}
+"#, min)).unwrap();
+            assert!(cfg.validate_team_rules().is_err(), "{} was accepted", min);
+        }
+
+        // the lead is a member too
+        let cfg = read_mscbot_cfg_from(r#"
+[fcp_behaviors]
+
+[teams.T-core]
+members = ["alice", "bob"]
+leads = ["carol"]
+min_reviewers = 3
+
+[teams.T-spec]
+ping = "org/spec"
+fetch_members = true
+min_reviewers = 5
+"#).unwrap();
+        assert!(cfg.validate_team_rules().is_ok());
+    }
+
+    #[test]
+    fn quorum_policies_met() {
+        use self::QuorumPolicy::*;
+
+        // Nothing is met without any reviews:
+        for &policy in &[Rfcbot, Majority, Unanimous, NOfM { n: 0 },
+                         Percentage { percent: 0 }, AllButK { k: 5 }] {
+            assert!(!policy.is_met(0, 0));
+            assert!(!policy.is_met(0, 3));
+        }
+
+        assert!(Rfcbot.is_met(5, 2));
+        assert!(!Rfcbot.is_met(5, 3));
+        assert!(!Rfcbot.is_met(2, 2));
+
+        assert!(Majority.is_met(3, 2));
+        assert!(!Majority.is_met(2, 2));
+
+        assert!(Unanimous.is_met(4, 0));
+        assert!(!Unanimous.is_met(4, 1));
+
+        assert!(NOfM { n: 3 }.is_met(3, 7));
+        assert!(!NOfM { n: 3 }.is_met(2, 7));
+        assert!(NOfM { n: 3 }.is_met(2, 0));
+
+        assert!(Percentage { percent: 75 }.is_met(3, 1));
+        assert!(!Percentage { percent: 75 }.is_met(2, 1));
+
+        assert!(AllButK { k: 1 }.is_met(4, 1));
+        assert!(!AllButK { k: 1 }.is_met(4, 2));
     }
 
     #[test]
     fn cfg_file_wellformed() {
-        // Just parse it and ensure that we get no panics for now!
+        // Just parse it and ensure that we get no errors for now!
         // This is a crap test; but, better than nothing.
-        let _ = read_mscbot_cfg();
+        read_mscbot_cfg(concat!(env!("CARGO_MANIFEST_DIR"), "/mscbot.toml")).unwrap();
     }
 
     #[test]
     fn team_members_exist() {
-        for (label, _) in SETUP.teams.iter() {
+        for (label, _) in SETUP.get().teams.iter() {
             println!("found team {:?}", label);
         }
     }